@@ -111,7 +111,7 @@ impl AnimationHandler {
 }
 
 pub fn render_intro(frame: &mut Frame, state: &mut State) {
-    if state.data.opened_once {
+    if state.opened_once {
         return;
     }
     let logo = r"
@@ -134,12 +134,12 @@ pub fn render_intro(frame: &mut Frame, state: &mut State) {
 
     Block::bordered()
         .border_type(BorderType::Rounded)
-        .fg(theme::BLUE)
+        .fg(theme::blue())
         .render(area, frame.buffer_mut());
 
     Paragraph::new(logo)
         .centered()
-        .fg(theme::BLUE)
+        .fg(theme::blue())
         .render(inner, frame.buffer_mut());
 
     let dur = 500;
@@ -151,7 +151,7 @@ pub fn render_intro(frame: &mut Frame, state: &mut State) {
             tachyonfx::Motion::UpToDown,
             10,
             1,
-            theme::BG0,
+            theme::bg0(),
             FxDuration::from_millis(dur)
         ),
         area,
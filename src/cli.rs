@@ -0,0 +1,303 @@
+use crate::{
+    config::Config,
+    daemon::{self, Client},
+    data::SaveData,
+    export::{self, Filter, Format},
+    import::{self, Source},
+    journal::{self, Event, Journal},
+    log::{self, Log, LogList},
+};
+use anyhow::{Ok, Result};
+use chrono::{Local, NaiveDate};
+use clap::{Parser, Subcommand};
+use serde_json::{Value, json};
+use std::{io, path::PathBuf};
+
+#[derive(Parser, Debug)]
+#[command(version, about = "A terminal time tracker")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Start a new log, e.g. `kairotui start "fix bug tag: backend"`
+    Start {
+        /// Log name followed by any number of `tag: <name>`
+        input: Vec<String>,
+    },
+    /// Complete an active log (the most recently started one by default)
+    Stop {
+        /// Index of the active log as shown by `list`
+        index: Option<usize>,
+        /// Complete every active log
+        #[arg(long, conflicts_with = "index")]
+        all: bool,
+    },
+    /// List active logs, or completed ones with `--past`
+    List {
+        #[arg(long)]
+        past: bool,
+    },
+    /// Show the currently running logs
+    Status,
+    /// Export active and past logs
+    Export {
+        #[arg(short, long, value_enum, default_value = "csv")]
+        format: Format,
+        /// First day to include (YYYY-MM-DD)
+        #[arg(long, value_parser = export::parse_date)]
+        from: Option<NaiveDate>,
+        /// Last day to include (YYYY-MM-DD)
+        #[arg(long, value_parser = export::parse_date)]
+        to: Option<NaiveDate>,
+        /// Only include logs with this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Import history from another time tracker
+    Import {
+        #[arg(short, long, value_enum)]
+        format: Source,
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Import entries that overlap existing logs instead of skipping them
+        #[arg(long)]
+        allow_overlap: bool,
+        /// Report what would be imported without saving
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// List every recorded change, or show the logs as they were at a moment
+    Journal {
+        /// `YYYY-MM-DD HH:MM`, `HH:MM` today, or relative like `-2h`
+        #[arg(long, allow_hyphen_values = true)]
+        at: Option<String>,
+    },
+    /// Keep the logs running in the background and serve them to other
+    /// kairotui processes over a socket next to the data file
+    Daemon,
+}
+
+pub fn run(cmd: Command, config: &Config) -> Result<()> {
+    if let Command::Daemon = cmd {
+        return daemon::run(config);
+    }
+    if let Some(mut client) = Client::connect(&config.data_path()?)
+        && remote(&cmd, &mut client)?
+    {
+        return Ok(());
+    }
+
+    let path = config.data_path()?;
+    let (mut data, recovered) = match SaveData::connect(config.storage, &path)? {
+        Some(data) => (data, None),
+        None => SaveData::open_or_recover(config.storage, path)?,
+    };
+    if let Some(backup) = recovered {
+        eprintln!("save file was corrupt, restored from {}", backup.display());
+    }
+    log::update_logs(&mut data.logs);
+
+    let mutates = matches!(
+        cmd,
+        Command::Start { .. }
+            | Command::Stop { .. }
+            | Command::Import { dry_run: false, .. }
+    );
+    match cmd {
+        Command::Start { input } => {
+            let log = start_log(&mut data, &input.join(" "), &config.default_tags)?;
+            println!("Started {}", format_log(log));
+        }
+        Command::Stop { index, all } => {
+            for log in stop_logs(&mut data, index, all)? {
+                println!("Stopped {}", format_log(&log));
+            }
+        }
+        Command::List { past } => {
+            print_list(if past { &data.past_logs } else { &data.logs });
+        }
+        Command::Status => print_status(&data.logs),
+        Command::Export {
+            format,
+            from,
+            to,
+            tags,
+            output,
+        } => {
+            let filter = Filter::new(from, to, tags);
+            match output {
+                Some(path) => {
+                    let n = export::write_file(&path, &data, format, &filter)?;
+                    eprintln!("Exported {n} logs to {}", path.display());
+                }
+                None => {
+                    export::write(&mut io::stdout().lock(), &data, format, &filter)?;
+                }
+            }
+        }
+        Command::Import {
+            format,
+            files,
+            allow_overlap,
+            ..
+        } => {
+            let mut entries = vec![];
+            for f in &files {
+                entries.extend(import::read(format, f)?);
+            }
+            let report = import::merge(&mut data, entries, allow_overlap);
+            for d in &report.duplicates {
+                println!("duplicate, skipped: {d}");
+            }
+            for c in &report.conflicts {
+                println!("conflict, skipped: {c}");
+            }
+            println!(
+                "Imported {} logs ({} duplicates, {} conflicts)",
+                report.added,
+                report.duplicates.len(),
+                report.conflicts.len()
+            );
+        }
+        Command::Journal { at } => {
+            let events = journal::read(&Journal::path_for(&config.data_path()?))?;
+            print_journal(&events, at.as_deref())?;
+        }
+        Command::Daemon => unreachable!("handled above"),
+    }
+
+    if mutates {
+        data.save()?;
+    }
+    Ok(())
+}
+
+/// Runs `cmd` through a running daemon. Returns false for commands the
+/// daemon doesn't serve.
+fn remote(cmd: &Command, client: &mut Client) -> Result<bool> {
+    match cmd {
+        Command::Start { input } => {
+            let log = client.call("start", json!({ "input": input.join(" ") }))?;
+            println!("Started {}", format_log(&serde_json::from_value(log)?));
+        }
+        Command::Stop { index, all } => {
+            let logs = client.call("stop", json!({ "index": index, "all": all }))?;
+            for log in serde_json::from_value::<Vec<Log>>(logs)? {
+                println!("Stopped {}", format_log(&log));
+            }
+        }
+        Command::List { past } => {
+            let logs = client.call("list", json!({ "past": past }))?;
+            print_list(&serde_json::from_value::<Vec<Log>>(logs)?);
+        }
+        Command::Status => {
+            let logs = client.call("list", json!({}))?;
+            print_status(&serde_json::from_value::<Vec<Log>>(logs)?);
+        }
+        Command::Journal { at } => {
+            let events = client.call("journal", Value::Null)?;
+            let events: Vec<Event> = serde_json::from_value(events)?;
+            print_journal(&events, at.as_deref())?;
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Prints every journal event, or the logs as they were `at` a moment.
+fn print_journal(events: &[Event], at: Option<&str>) -> Result<()> {
+    let Some(at) = at else {
+        for e in events {
+            let at = e.at.format("%Y-%m-%d %H:%M:%S");
+            println!("{at}  {}", e.change.describe());
+        }
+        return Ok(());
+    };
+    let now = Local::now();
+    let state = journal::state_at(events, log::parse_when(at, now, now)?)?;
+    for (title, logs) in [("Active", &state.logs), ("Past", &state.past_logs)] {
+        println!("{title}:");
+        for (i, l) in logs.iter().enumerate() {
+            println!("{i:>3}  {}", format_log(l));
+        }
+    }
+    Ok(())
+}
+
+/// Starts a log from `start` input, a name followed by `tag: <name>`s.
+pub fn start_log<'a>(
+    data: &'a mut SaveData,
+    input: &str,
+    default_tags: &[String],
+) -> Result<&'a Log> {
+    if input.trim().is_empty() {
+        anyhow::bail!("log name must not be empty.");
+    }
+    LogList::handle_add(input.to_string(), data, default_tags);
+    Ok(data.logs.last().unwrap())
+}
+
+/// Completes active log `index`, the most recent one by default, or all of
+/// them. Returns the completed logs.
+pub fn stop_logs(
+    data: &mut SaveData,
+    index: Option<usize>,
+    all: bool,
+) -> Result<Vec<Log>> {
+    if data.logs.is_empty() {
+        anyhow::bail!("no active logs.");
+    }
+    let indices: Vec<usize> = if all {
+        (0..data.logs.len()).rev().collect()
+    } else {
+        vec![index.unwrap_or(data.logs.len() - 1)]
+    };
+    let mut stopped = vec![];
+    for i in indices {
+        let Some(log) = log::complete_log(data, i) else {
+            anyhow::bail!("no active log at index {i}.");
+        };
+        stopped.push(log.clone());
+    }
+    Ok(stopped)
+}
+
+fn print_list(logs: &[Log]) {
+    for (i, l) in logs.iter().enumerate() {
+        println!("{i:>3}  {}", format_log(l));
+    }
+}
+
+fn print_status(logs: &[Log]) {
+    if logs.is_empty() {
+        println!("No active logs.");
+    }
+    for l in logs {
+        let state = if l.paused { "paused " } else { "running" };
+        println!("{state}  {}", format_log(l));
+    }
+}
+
+fn format_log(log: &Log) -> String {
+    let mut tags: Vec<&str> = log.tags.iter().map(|t| t.as_str()).collect();
+    tags.sort();
+    let start = log.start.format("%Y-%m-%d %H:%M");
+    let tags = if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", tags.join(", "))
+    };
+    format!(
+        "{}  {}  {}{}",
+        start,
+        log::duration_as_hhmmss(log.duration()),
+        log.name,
+        tags
+    )
+}
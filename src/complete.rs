@@ -0,0 +1,245 @@
+use crate::{data::SaveData, theme};
+use chrono::{DateTime, Local};
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Clear, List, ListItem, ListState},
+};
+use regex::Regex;
+use std::collections::HashMap;
+
+const MAX_ITEMS: usize = 6;
+
+/// Tag completions offered while typing `tag: <partial>` in the input dialog.
+#[derive(Debug, Default)]
+pub struct Completion {
+    pub items: Vec<String>,
+    /// Length in chars of the partial tag name before the cursor.
+    pub partial_len: usize,
+    pub state: ListState,
+}
+
+impl Completion {
+    pub fn is_active(&self) -> bool {
+        !self.items.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.partial_len = 0;
+        self.state = ListState::default();
+    }
+
+    /// Recomputes completions for the text before the cursor.
+    pub fn update(&mut self, before_cursor: &str, data: &SaveData) {
+        let regex = Regex::new(r"tag:\s([\w:/]*)$").unwrap();
+        let Some(partial) = regex.captures(before_cursor).map(|c| c[1].to_string())
+        else {
+            self.clear();
+            return;
+        };
+
+        let prev = self.selected().map(str::to_string);
+        self.items = candidates(&partial, data);
+        self.partial_len = partial.chars().count();
+        // an exact match needs no completion
+        if self.items.len() == 1 && self.items[0] == partial {
+            self.items.clear();
+        }
+        let idx = prev
+            .and_then(|p| self.items.iter().position(|i| *i == p))
+            .unwrap_or(0);
+        self.state.select((!self.items.is_empty()).then_some(idx));
+    }
+
+    pub fn selected(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .map(String::as_str)
+    }
+
+    pub fn select_next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1) % self.items.len()));
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            let len = self.items.len();
+            self.state.select(Some((i + len - 1) % len));
+        }
+    }
+}
+
+/// Scores `candidate` as a fuzzy (subsequence) match of `query`. Consecutive
+/// and leading matches score higher; `None` if it doesn't match at all.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+
+    for q in query.to_lowercase().chars() {
+        let found = candidate[pos..].iter().position(|c| *c == q)? + pos;
+        score += match last {
+            Some(l) if l + 1 == found => 5,
+            _ if found == 0 => 8,
+            _ => 1,
+        };
+        score -= (found - pos) as i32;
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Existing tags matching `partial`, best matches first, then by `refs` and
+/// how recently they were used.
+pub fn candidates(partial: &str, data: &SaveData) -> Vec<String> {
+    let mut last_used: HashMap<&str, DateTime<Local>> = HashMap::new();
+    for log in data.logs.iter().chain(data.past_logs.iter()) {
+        for t in &log.tags {
+            let e = last_used.entry(t.as_str()).or_insert(log.start);
+            *e = (*e).max(log.start);
+        }
+    }
+
+    let mut matches: Vec<(i32, i32, Option<DateTime<Local>>, &str)> = data
+        .tags
+        .tags()
+        .iter()
+        .filter_map(|t| {
+            let score = fuzzy_score(partial, t.name())?;
+            Some((score, t.refs, last_used.get(t.name()).copied(), t.name()))
+        })
+        .collect();
+    matches.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+    matches
+        .into_iter()
+        .take(MAX_ITEMS)
+        .map(|m| m.3.to_string())
+        .collect()
+}
+
+/// Renders the completion popup just below the input dialog at `anchor`.
+pub fn render(
+    completion: &mut Completion,
+    anchor: Rect,
+    data: &SaveData,
+    frame: &mut Frame,
+) {
+    let width = completion
+        .items
+        .iter()
+        .map(|i| i.chars().count() as u16 + 6)
+        .max()
+        .unwrap_or(0)
+        .max(20);
+    let screen = frame.area();
+    let area = Rect {
+        x: anchor.x,
+        y: anchor.y + anchor.height,
+        width,
+        height: completion.items.len() as u16 + 2,
+    }
+    .intersection(screen);
+
+    let items = completion.items.iter().map(|name| {
+        let color = data
+            .tags
+            .tags()
+            .iter()
+            .find(|t| t.name() == name)
+            .map_or(theme::text(), |t| Color::from_u32(*t.color()));
+        ListItem::from(Line::from(vec![
+            theme::unicode_icon(0xf1224, color),
+            Span::raw(name.clone()),
+        ]))
+    });
+
+    frame.render_widget(Clear, area);
+    let list = List::new(items)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .fg(theme::orange())
+                .title(" Tags "),
+        )
+        .fg(theme::text())
+        .bg(theme::bg0())
+        .highlight_style(Style::default().bg(theme::bg1()));
+    frame.render_stateful_widget(list, area, &mut completion.state);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Log;
+    use chrono::{TimeDelta, TimeZone};
+
+    /// Data with `tags` as `(name, refs)` and a past log using every tag in
+    /// `used`, the later ones more recently.
+    fn data(tags: &[(&str, i32)], used: &[&str]) -> SaveData {
+        let mut data = SaveData::new();
+        for (name, refs) in tags {
+            data.tags.add(name).refs = *refs;
+        }
+        let day = Local.with_ymd_and_hms(2025, 1, 6, 9, 0, 0).unwrap();
+        for (i, tag) in used.iter().enumerate() {
+            let mut log = Log::new("work".into(), [tag.to_string()].into());
+            let start = day + TimeDelta::hours(i as i64);
+            log.set_span(start, start + TimeDelta::minutes(30));
+            data.past_logs.push(log);
+        }
+        data
+    }
+
+    #[test]
+    fn scores_subsequence_matches() {
+        assert_eq!(fuzzy_score("", "backend"), Some(0));
+        assert!(fuzzy_score("bend", "backend").is_some());
+        assert!(fuzzy_score("BACK", "backend").is_some());
+        assert_eq!(fuzzy_score("x", "backend"), None);
+        assert_eq!(fuzzy_score("kb", "backend"), None);
+        assert_eq!(fuzzy_score("backends", "backend"), None);
+    }
+
+    #[test]
+    fn leading_and_consecutive_matches_score_higher() {
+        let score = |q| fuzzy_score(q, "backend").unwrap();
+        assert!(score("back") > score("bknd"));
+        assert!(score("ba") > score("ac"));
+        assert!(score("end") > score("ed"));
+        assert!(fuzzy_score("ba", "bar").unwrap() > fuzzy_score("ba", "xbxa").unwrap());
+    }
+
+    #[test]
+    fn ranks_by_score_then_refs_then_recency() {
+        let data = data(
+            &[("xbxa", 9), ("bar", 0), ("baz", 2), ("bat", 2), ("bay", 2)],
+            &["bat", "baz", "bay"],
+        );
+        assert_eq!(
+            candidates("ba", &data),
+            ["bay", "baz", "bat", "bar", "xbxa"]
+        );
+    }
+
+    #[test]
+    fn tags_that_do_not_match_are_left_out() {
+        let data = data(&[("backend", 1), ("frontend", 1)], &[]);
+        assert_eq!(candidates("back", &data), ["backend"]);
+        assert!(candidates("qq", &data).is_empty());
+    }
+
+    #[test]
+    fn offers_at_most_a_few_tags() {
+        let names: Vec<String> = (0..10).map(|i| format!("t{i}")).collect();
+        let tags: Vec<(&str, i32)> = names.iter().map(|n| (n.as_str(), 0)).collect();
+        assert_eq!(candidates("t", &data(&tags, &[])).len(), MAX_ITEMS);
+    }
+}
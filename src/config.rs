@@ -0,0 +1,150 @@
+use crate::{
+    data::DEFAULT_BACKUPS,
+    keymap::{Action, KeySpec, Keymap},
+    storage::Backend,
+    tag, theme,
+};
+use anyhow::{Context, Result};
+use dirs::{config_dir, data_local_dir, home_dir};
+use regex::Regex;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// `config_dir()/kairotui/config.toml`; every field is optional.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub data_file: Option<String>,
+    /// `json` (the default) or `sqlite`.
+    pub storage: Backend,
+    pub log_dir: Option<String>,
+    pub log_level: String,
+    pub backups: usize,
+    pub default_tags: Vec<String>,
+    pub theme: String,
+    pub animations: bool,
+    pub keys: BTreeMap<String, KeySpec>,
+    /// Actions that ask for confirmation first.
+    pub confirm: Vec<String>,
+    #[serde(skip)]
+    pub keymap: Keymap,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_file: None,
+            storage: Backend::Json,
+            log_dir: None,
+            log_level: "info".to_string(),
+            backups: DEFAULT_BACKUPS,
+            default_tags: vec![],
+            theme: "gruvbox".to_string(),
+            animations: true,
+            keys: BTreeMap::new(),
+            confirm: vec!["delete".to_string()],
+            keymap: Keymap::default(),
+        }
+    }
+}
+
+pub fn dir() -> Result<PathBuf> {
+    let mut path = config_dir().context("could not determine config directory.")?;
+    path.push("kairotui");
+    Ok(path)
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_path(path: &str) -> Result<PathBuf> {
+    Ok(match path.strip_prefix("~/") {
+        Some(p) => home_dir().context("no home directory")?.join(p),
+        None => PathBuf::from(path),
+    })
+}
+
+impl Config {
+    /// Loads the config file, falling back to defaults for a missing file.
+    /// A broken file also yields the defaults, along with the error so it
+    /// can be shown to the user.
+    pub fn load() -> (Config, Option<anyhow::Error>) {
+        match Config::try_load() {
+            Ok(config) => (config, None),
+            Err(e) => {
+                let mut config = Config::default();
+                let _ = config.validate();
+                (config, Some(e))
+            }
+        }
+    }
+
+    fn try_load() -> Result<Config> {
+        let path = dir()?.join("config.toml");
+        let mut config = if fs::exists(&path)? {
+            let text = fs::read_to_string(&path)?;
+            toml::from_str(&text)
+                .with_context(|| format!("invalid config {}", path.display()))?
+        } else {
+            Config::default()
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&mut self) -> Result<()> {
+        if !["trace", "debug", "info", "warn", "error"]
+            .contains(&self.log_level.as_str())
+        {
+            anyhow::bail!("invalid log_level '{}'", self.log_level);
+        }
+        let tag_re = Regex::new(&format!("^{}$", tag::NAME_PATTERN)).unwrap();
+        if let Some(t) = self.default_tags.iter().find(|t| !tag_re.is_match(t)) {
+            anyhow::bail!("invalid default tag '{t}'");
+        }
+        self.default_tags = self
+            .default_tags
+            .iter()
+            .map(|t| tag::normalize(t))
+            .collect();
+
+        self.keymap = Keymap::new(&self.keys)?;
+        if let Some(a) = self.confirm.iter().find(|a| Action::from_name(a).is_none()) {
+            anyhow::bail!("unknown action '{a}' in confirm");
+        }
+        // only once everything else is valid, as the palette can't be reset
+        theme::set(&self.theme)
+    }
+
+    pub fn data_path(&self) -> Result<PathBuf> {
+        match &self.data_file {
+            Some(path) => {
+                let path = expand_path(path)?;
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                Ok(path)
+            }
+            None => crate::data::default_path(self.storage),
+        }
+    }
+
+    pub fn log_path(&self) -> Result<PathBuf> {
+        match &self.log_dir {
+            Some(path) => expand_path(path),
+            None => {
+                let mut path =
+                    data_local_dir().context("could not determine data directory.")?;
+                path.push("kairotui");
+                path.push("logs");
+                Ok(path)
+            }
+        }
+    }
+
+    pub fn confirms(&self, action: Action) -> bool {
+        self.confirm.iter().any(|a| a == action.name())
+    }
+
+    pub fn log_level(&self) -> tracing::Level {
+        self.log_level.parse().unwrap_or(tracing::Level::INFO)
+    }
+}
@@ -0,0 +1,450 @@
+use crate::{
+    cli,
+    config::Config,
+    data::SaveData,
+    journal::{self, Journal, Patch},
+    log,
+};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    net::Shutdown,
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// How often the daemon checks the data file for changes made elsewhere.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the daemon waits for a client to take a message before
+/// dropping it, so one stuck client can't stall the others.
+const SEND_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long a client waits for the daemon to answer.
+const CALL_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// The request was valid but couldn't be carried out.
+const FAILED: i64 = -32000;
+
+/// The daemon's socket lives next to the data file it owns.
+pub fn socket_path(data_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.sock", data_path.display()))
+}
+
+/// A JSON-RPC 2.0 request, one per line.
+#[derive(Debug, Deserialize)]
+struct Request {
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct StartParams {
+    input: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct StopParams {
+    index: Option<usize>,
+    all: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ListParams {
+    past: bool,
+}
+
+/// What connection threads tell the daemon loop.
+enum Message {
+    Connected(u64, UnixStream),
+    Request(u64, Result<Request, String>),
+    Closed(u64),
+}
+
+fn send(stream: &mut UnixStream, msg: &Value) -> Result<()> {
+    let mut line = serde_json::to_string(msg)?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+fn error(id: Option<Value>, code: i64, message: String) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, (i64, String)> {
+    serde_json::to_value(value).map_err(|e| (FAILED, e.to_string()))
+}
+
+fn params<T: for<'de> Deserialize<'de> + Default>(params: Value) -> Result<T, String> {
+    if params.is_null() {
+        return Ok(T::default());
+    }
+    serde_json::from_value(params).map_err(|e| e.to_string())
+}
+
+/// Accepts connections and turns every line they send into a [`Message`].
+fn listen(listener: UnixListener, tx: Sender<Message>) {
+    for (id, stream) in (0..).zip(listener.incoming()) {
+        let Some(stream) = stream.ok().and_then(|s| Some((s.try_clone().ok()?, s)))
+        else {
+            continue;
+        };
+        let (reader, writer) = stream;
+        if tx.send(Message::Connected(id, writer)).is_err() {
+            return;
+        }
+        let tx = tx.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                let Some(line) = line.ok() else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let req = serde_json::from_str(&line).map_err(|e| e.to_string());
+                if tx.send(Message::Request(id, req)).is_err() {
+                    return;
+                }
+            }
+            let _ = tx.send(Message::Closed(id));
+        });
+    }
+}
+
+/// Owns the data while it runs: keeps the timers going, serves requests on
+/// the socket next to the data file and tells subscribers about changes,
+/// including those other processes make to the file.
+pub fn run(config: &Config) -> Result<()> {
+    let path = config.data_path()?;
+    let socket = socket_path(&path);
+    if fs::exists(&socket)? {
+        if UnixStream::connect(&socket).is_ok() {
+            anyhow::bail!("a daemon is already listening on {}", socket.display());
+        }
+        fs::remove_file(&socket)?;
+    }
+    let (mut data, _) = SaveData::open_or_recover(config.storage, path)?;
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("failed to listen on {}", socket.display()))?;
+    info!("daemon listening on {}", socket.display());
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || listen(listener, tx));
+    let res = serve(rx, &mut data, config);
+    let _ = fs::remove_file(&socket);
+    res
+}
+
+fn serve(rx: Receiver<Message>, data: &mut SaveData, config: &Config) -> Result<()> {
+    let mut clients: HashMap<u64, UnixStream> = HashMap::new();
+    let mut subscribers: HashSet<u64> = HashSet::new();
+    let mut last_reload = Instant::now();
+
+    loop {
+        let mut changed = false;
+        match rx.recv_timeout(RELOAD_INTERVAL) {
+            Ok(Message::Connected(id, stream)) => {
+                if stream.set_write_timeout(Some(SEND_TIMEOUT)).is_ok() {
+                    clients.insert(id, stream);
+                }
+            }
+            Ok(Message::Closed(id)) => {
+                clients.remove(&id);
+                subscribers.remove(&id);
+            }
+            Ok(Message::Request(id, req)) => {
+                log::update_logs(&mut data.logs);
+                let reply = match req {
+                    Err(e) => error(None, PARSE_ERROR, e),
+                    Ok(req) => {
+                        if req.method == "subscribe" {
+                            subscribers.insert(id);
+                        }
+                        let req_id = req.id.clone();
+                        match handle(req, data, config) {
+                            Err((code, e)) => error(req_id, code, e),
+                            Ok((result, modified)) => {
+                                changed |= modified;
+                                json!({ "jsonrpc": "2.0", "id": req_id, "result": result })
+                            }
+                        }
+                    }
+                };
+                if let Some(stream) = clients.get_mut(&id)
+                    && send(stream, &reply).is_err()
+                {
+                    drop_client(&mut clients, id);
+                    subscribers.remove(&id);
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        if changed {
+            if let Err(e) = data.save() {
+                warn!("daemon failed to save: {e:#}");
+            }
+        } else if last_reload.elapsed() >= RELOAD_INTERVAL {
+            last_reload = Instant::now();
+            if data.changed_on_disk() {
+                changed = data.save().unwrap_or_else(|e| {
+                    warn!("daemon failed to reload: {e:#}");
+                    false
+                });
+            }
+        }
+
+        if changed {
+            log::update_logs(&mut data.logs);
+            let note = json!({
+                "jsonrpc": "2.0",
+                "method": "changed",
+                "params": { "logs": data.logs },
+            });
+            let slow: Vec<u64> = subscribers
+                .iter()
+                .copied()
+                .filter(|id| {
+                    clients
+                        .get_mut(id)
+                        .is_none_or(|stream| send(stream, &note).is_err())
+                })
+                .collect();
+            for id in slow {
+                warn!("dropping subscriber {id} that stopped reading");
+                drop_client(&mut clients, id);
+                subscribers.remove(&id);
+            }
+        }
+    }
+}
+
+/// Closes the connection of a client that can't be written to, which also
+/// ends the thread reading from it.
+fn drop_client(clients: &mut HashMap<u64, UnixStream>, id: u64) {
+    if let Some(stream) = clients.remove(&id) {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Runs one request, returning its result and whether it changed the data.
+fn handle(
+    req: Request,
+    data: &mut SaveData,
+    config: &Config,
+) -> Result<(Value, bool), (i64, String)> {
+    let failed = |e: anyhow::Error| (FAILED, format!("{e:#}"));
+    let invalid = |e: String| (INVALID_PARAMS, e);
+    match req.method.as_str() {
+        "start" => {
+            let p: StartParams = serde_json::from_value(req.params)
+                .map_err(|e| invalid(e.to_string()))?;
+            let log =
+                cli::start_log(data, &p.input, &config.default_tags).map_err(failed)?;
+            Ok((to_value(log)?, true))
+        }
+        "stop" => {
+            let p: StopParams = params(req.params).map_err(invalid)?;
+            let logs = cli::stop_logs(data, p.index, p.all).map_err(failed)?;
+            Ok((to_value(&logs)?, true))
+        }
+        "list" => {
+            let p: ListParams = params(req.params).map_err(invalid)?;
+            let logs = if p.past { &data.past_logs } else { &data.logs };
+            Ok((to_value(logs)?, false))
+        }
+        "state" => Ok((to_value(data.tracked())?, false)),
+        "apply" => {
+            let patch: Patch = serde_json::from_value(req.params)
+                .map_err(|e| invalid(e.to_string()))?;
+            let mut state = data.tracked();
+            patch.apply(&mut state);
+            data.logs = state.logs;
+            data.past_logs = state.past_logs;
+            data.tags = state.tags;
+            Ok((to_value(data.tracked())?, !patch.is_empty()))
+        }
+        "journal" => {
+            let path = data
+                .save_path
+                .as_deref()
+                .ok_or((FAILED, "no save path set.".into()))?;
+            let events =
+                journal::read(&Journal::path_for(Path::new(path))).map_err(failed)?;
+            Ok((to_value(events)?, false))
+        }
+        "subscribe" => Ok((json!(true), false)),
+        m => Err((METHOD_NOT_FOUND, format!("unknown method '{m}'"))),
+    }
+}
+
+/// Whether `e` means the daemon went away or stopped answering, as opposed
+/// to it refusing a request.
+pub fn is_disconnect(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| c.is::<io::Error>())
+}
+
+/// A connection to a running daemon.
+#[derive(Debug)]
+pub struct Client {
+    writer: UnixStream,
+    reader: BufReader<UnixStream>,
+    next_id: u64,
+}
+
+impl Client {
+    /// Connects to the daemon for the data file at `data_path`, or returns
+    /// `None` if none is running.
+    pub fn connect(data_path: &Path) -> Option<Client> {
+        let writer = UnixStream::connect(socket_path(data_path)).ok()?;
+        writer.set_read_timeout(Some(CALL_TIMEOUT)).ok()?;
+        writer.set_write_timeout(Some(CALL_TIMEOUT)).ok()?;
+        let reader = BufReader::new(writer.try_clone().ok()?);
+        Some(Client {
+            writer,
+            reader,
+            next_id: 0,
+        })
+    }
+
+    /// Sends a request and waits for its result, skipping notifications.
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        self.next_id += 1;
+        let id = self.next_id;
+        send(
+            &mut self.writer,
+            &json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }),
+        )?;
+        loop {
+            let msg = self.read()?;
+            if msg["id"] != json!(id) {
+                continue;
+            }
+            if let Some(e) = msg.get("error") {
+                anyhow::bail!("{}", e["message"].as_str().unwrap_or("daemon error"));
+            }
+            return Ok(msg["result"].clone());
+        }
+    }
+
+    fn read(&mut self) -> Result<Value> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof))
+                .context("daemon closed the connection");
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// Subscribes to changes and signals each one on the returned channel
+    /// from a background thread, until the daemon goes away.
+    pub fn watch(mut self) -> Result<Receiver<()>> {
+        self.call("subscribe", Value::Null)?;
+        // changes can be hours apart
+        self.reader.get_ref().set_read_timeout(None)?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(msg) = self.read() {
+                if msg["method"] == "changed" && tx.send(()).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(
+        method: &str,
+        params: Value,
+        data: &mut SaveData,
+    ) -> Result<(Value, bool), (i64, String)> {
+        let req = Request {
+            id: Some(json!(1)),
+            method: method.to_string(),
+            params,
+        };
+        handle(req, data, &Config::default())
+    }
+
+    fn names(logs: &Value) -> Vec<&str> {
+        logs.as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["name"].as_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn start_stop_and_list() {
+        let mut data = SaveData::new();
+        let (log, changed) =
+            call("start", json!({ "input": "a tag: x" }), &mut data).unwrap();
+        assert!(changed);
+        assert_eq!(log["name"], "a");
+        call("start", json!({ "input": "b" }), &mut data).unwrap();
+
+        let (active, changed) = call("list", Value::Null, &mut data).unwrap();
+        assert!(!changed);
+        assert_eq!(names(&active), ["a", "b"]);
+
+        let (stopped, changed) = call("stop", json!({ "index": 0 }), &mut data).unwrap();
+        assert!(changed);
+        assert_eq!(names(&stopped), ["a"]);
+        let (past, _) = call("list", json!({ "past": true }), &mut data).unwrap();
+        assert_eq!(names(&past), ["a"]);
+    }
+
+    #[test]
+    fn rejects_bad_requests() {
+        let mut data = SaveData::new();
+        let (code, _) = call("frobnicate", Value::Null, &mut data).unwrap_err();
+        assert_eq!(code, METHOD_NOT_FOUND);
+        let (code, _) = call("start", json!({ "name": "a" }), &mut data).unwrap_err();
+        assert_eq!(code, INVALID_PARAMS);
+        let (code, _) = call("stop", Value::Null, &mut data).unwrap_err();
+        assert_eq!(code, FAILED);
+    }
+
+    #[test]
+    fn applies_patches_from_clients() {
+        let mut data = SaveData::new();
+        call("start", json!({ "input": "a" }), &mut data).unwrap();
+        let (state, _) = call("state", Value::Null, &mut data).unwrap();
+        let base: journal::Tracked = serde_json::from_value(state).unwrap();
+
+        // the client renames its copy while the daemon starts another log
+        let mut ours = base.clone();
+        ours.logs[0].name = "renamed".to_string();
+        call("start", json!({ "input": "b" }), &mut data).unwrap();
+
+        let patch = Patch::between(&base, &ours);
+        let (state, changed) =
+            call("apply", serde_json::to_value(&patch).unwrap(), &mut data).unwrap();
+        assert!(changed);
+        assert_eq!(names(&state["logs"]), ["renamed", "b"]);
+        assert_eq!(data.logs[0].name, "renamed");
+    }
+}
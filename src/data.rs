@@ -1,43 +1,628 @@
-use anyhow::{Ok, Result};
+use anyhow::{Context, Ok, Result};
+use chrono::Local;
+use dirs::config_dir;
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{
+    fs::{self, File, OpenOptions, TryLockError},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+use tracing::{info, warn};
 
-use crate::{log::Log, tag::TagSys};
+use crate::{
+    daemon::{self, Client},
+    journal::{self, COMPACT_EVERY, Journal, Patch, Tracked},
+    log::Log,
+    migrate::CURRENT_VERSION,
+    storage::{self, Backend, Storage},
+    tag::TagSys,
+};
+
+pub const DEFAULT_BACKUPS: usize = 5;
+const BACKUP_EXT: &str = "bak";
+/// How long a save waits for another process to finish writing.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Size and modification time of a file, or `None` if it doesn't exist.
+type Stat = Option<(u64, SystemTime)>;
+
+fn stat(path: &Path) -> Result<Stat> {
+    match fs::metadata(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        meta => {
+            let meta = meta?;
+            Ok(Some((meta.len(), meta.modified()?)))
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    fs::read(path)?.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// What the data file and journal looked like when this process last read
+/// or wrote them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Fingerprint {
+    data: Stat,
+    data_hash: u64,
+    journal: Stat,
+}
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct SaveData {
-    opened_once: bool,
+    pub version: u64,
+    pub opened_once: bool,
     pub logs: Vec<Log>,
     pub past_logs: Vec<Log>,
     pub tags: TagSys,
     pub save_path: Option<String>,
+    /// Number of journal events this snapshot includes.
+    pub journal_seq: u64,
+    #[serde(skip)]
+    storage: Option<Box<dyn Storage>>,
+    #[serde(skip)]
+    journal: Option<Journal>,
+    #[serde(skip)]
+    seen: Fingerprint,
+    /// Why changes are no longer saved, if they aren't.
+    #[serde(skip)]
+    read_only: Option<String>,
+    #[serde(skip)]
+    daemon: Option<Remote>,
+}
+
+/// The daemon that owns the data file, when one does, and the state it last
+/// sent.
+#[derive(Debug)]
+struct Remote {
+    client: Client,
+    backend: Backend,
+    synced: Tracked,
 }
 
 impl SaveData {
-    pub fn new(path: String) -> Self {
+    pub fn new() -> Self {
         Self {
-            save_path: Some(path),
+            version: CURRENT_VERSION,
             ..Default::default()
         }
     }
 
-    pub fn save(&self) -> Result<()> {
-        let data = serde_json::to_string(self).unwrap();
-        if let Some(path) = &self.save_path {
-            fs::write(path, data)?;
+    pub fn read_only(&self) -> Option<&str> {
+        self.read_only.as_deref()
+    }
+
+    fn path(&self) -> Result<&Path> {
+        self.save_path
+            .as_deref()
+            .map(Path::new)
+            .context("no save path set.")
+    }
+
+    /// Takes the advisory lock next to the data file, waiting up to
+    /// [`LOCK_TIMEOUT`] for another process to release it. The lock is
+    /// released when the returned file is dropped.
+    fn lock(path: &Path) -> Result<File> {
+        let lock_path = format!("{}.lock", path.display());
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)?;
+        let deadline = Instant::now() + LOCK_TIMEOUT;
+        while let Err(e) = file.try_lock() {
+            match e {
+                TryLockError::WouldBlock if Instant::now() < deadline => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                TryLockError::WouldBlock => {
+                    anyhow::bail!("{} is locked by another process", path.display())
+                }
+                TryLockError::Error(e) => return Err(e.into()),
+            }
+        }
+        Ok(file)
+    }
+
+    fn fingerprint(&self) -> Result<Fingerprint> {
+        let path = self.path()?;
+        let data = stat(path)?;
+        let data_hash = match data {
+            _ if data == self.seen.data => self.seen.data_hash,
+            Some(_) => hash_file(path)?,
+            None => 0,
+        };
+        Ok(Fingerprint {
+            data,
+            data_hash,
+            journal: stat(&Journal::path_for(path))?,
+        })
+    }
+
+    /// The logs and tags, as tracked by the journal.
+    pub fn tracked(&self) -> Tracked {
+        Tracked {
+            logs: self.logs.clone(),
+            past_logs: self.past_logs.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+
+    /// Whether another process wrote the data file or journal since this
+    /// one last read or wrote them.
+    pub fn changed_on_disk(&self) -> bool {
+        self.fingerprint().is_ok_and(|f| f != self.seen)
+    }
+
+    /// Appends what changed since the last save to the journal, and
+    /// compacts it into the data file every [`COMPACT_EVERY`] events.
+    /// Changes another process made in the meantime are merged in first;
+    /// returns whether there were any. Once they can't be merged the data
+    /// becomes read-only: it is still reloaded when it changes on disk, but
+    /// never written.
+    pub fn save(&mut self) -> Result<bool> {
+        if self.daemon.is_some() {
+            return self.save_remote();
+        }
+        if self.read_only.is_some() {
+            return self.reload();
+        }
+        let _lock = SaveData::lock(self.path()?)?;
+
+        let merged = if self.fingerprint()? != self.seen {
+            match self.merge_from_disk() {
+                Err(e) => {
+                    warn!("can't merge external changes: {e:#}");
+                    self.read_only = Some(format!("{e:#}"));
+                    return Err(e.context("data file changed on disk"));
+                }
+                merged => merged?,
+            }
         } else {
+            false
+        };
+
+        let Some(journal) = &mut self.journal else {
             anyhow::bail!("no save path set.");
+        };
+        journal.record(&self.logs, &self.past_logs, &self.tags)?;
+        if journal.end() - self.journal_seq >= COMPACT_EVERY {
+            self.compact_locked()?;
+        }
+        self.seen = self.fingerprint()?;
+        Ok(merged)
+    }
+
+    /// Replaces our data with what's on disk now, with our unsaved changes
+    /// merged on top. Returns whether anything else had changed.
+    fn merge_from_disk(&mut self) -> Result<bool> {
+        let storage = self.storage.as_ref().context("no save path set.")?;
+        let mut theirs = storage.load()?.unwrap_or_else(SaveData::new);
+        let journal = theirs.open_journal(&**storage, self.path()?)?;
+        let base = self.journal.as_ref().context("no save path set.")?.last();
+
+        let ours = Tracked {
+            logs: std::mem::take(&mut self.logs),
+            past_logs: std::mem::take(&mut self.past_logs),
+            tags: std::mem::take(&mut self.tags),
+        };
+        let foreign = base != journal.last();
+        let merged = journal::merge(base, &ours, journal.last());
+        self.logs = merged.logs;
+        self.past_logs = merged.past_logs;
+        self.tags = merged.tags;
+        self.journal_seq = theirs.journal_seq;
+        self.journal = Some(journal);
+        if foreign {
+            info!("merged changes made by another process");
         }
+        Ok(foreign)
+    }
+
+    /// Sends what changed since the daemon last sent its state, and takes
+    /// the state it answers with. Returns whether that has changes made by
+    /// others. Saves locally instead once the daemon is gone.
+    fn save_remote(&mut self) -> Result<bool> {
+        let ours = self.tracked();
+        let moved = self.fingerprint()? != self.seen;
+        let remote = self.daemon.as_mut().context("no daemon connected.")?;
+        let patch = Patch::between(&remote.synced, &ours);
+        if patch.is_empty() && !moved {
+            return Ok(false);
+        }
+        let state = remote
+            .client
+            .call("apply", serde_json::to_value(&patch)?)
+            .and_then(|state| Ok(serde_json::from_value::<Tracked>(state)?));
+        let state = match state {
+            Err(e) if daemon::is_disconnect(&e) => {
+                warn!("lost the daemon, saving locally: {e:#}");
+                return self.detach(ours);
+            }
+            state => state?,
+        };
+        let foreign = !Patch::between(&ours, &state).is_empty();
+        self.logs = state.logs.clone();
+        self.past_logs = state.past_logs.clone();
+        self.tags = state.tags.clone();
+        remote.synced = state;
+        self.seen = self.fingerprint()?;
+        Ok(foreign)
+    }
+
+    /// Opens the data file for ourselves after the daemon went away, with
+    /// the changes it didn't get merged in.
+    fn detach(&mut self, ours: Tracked) -> Result<bool> {
+        let remote = self.daemon.take().context("no daemon connected.")?;
+        let mut local = SaveData::open(remote.backend, self.path()?.to_path_buf())?;
+        let merged = journal::merge(&remote.synced, &ours, &local.tracked());
+        local.logs = merged.logs;
+        local.past_logs = merged.past_logs;
+        local.tags = merged.tags;
+        local.save()?;
+        *self = local;
+        Ok(true)
+    }
+
+    /// Replaces our data with what's on disk now, if it changed. Returns
+    /// whether it did.
+    fn reload(&mut self) -> Result<bool> {
+        let _lock = SaveData::lock(self.path()?)?;
+        let seen = self.fingerprint()?;
+        if seen == self.seen {
+            return Ok(false);
+        }
+        let storage = self.storage.as_ref().context("no save path set.")?;
+        let mut theirs = storage.load()?.unwrap_or_else(SaveData::new);
+        journal::replay(&Journal::path_for(self.path()?), &mut theirs)?;
+        self.logs = theirs.logs;
+        self.past_logs = theirs.past_logs;
+        self.tags = theirs.tags;
+        self.journal_seq = theirs.journal_seq;
+        self.seen = seen;
+        info!("reloaded read-only data");
+        Ok(true)
+    }
+
+    /// Writes the data file, folding every journal event into it.
+    pub fn compact(&mut self) -> Result<()> {
+        if self.read_only.is_some() || self.daemon.is_some() {
+            return Ok(());
+        }
+        let _lock = SaveData::lock(self.path()?)?;
+        if self.fingerprint()? != self.seen {
+            // another process wrote since, leave it to them
+            return Ok(());
+        }
+        self.compact_locked()?;
+        self.seen = self.fingerprint()?;
         Ok(())
     }
 
-    pub fn load(&self) -> Result<SaveData> {
-        if let Some(path) = &self.save_path {
-            let str: String = fs::read_to_string(path)?;
-            let dat: SaveData = serde_json::from_str(&str)?;
-            Ok(dat)
-        } else {
+    /// Also rotates the journal once it holds [`COMPACT_EVERY`] events.
+    fn compact_locked(&mut self) -> Result<()> {
+        let (Some(storage), Some(journal)) = (&self.storage, &self.journal) else {
             anyhow::bail!("no save path set.");
+        };
+        if self.journal_seq == journal.end() {
+            return Ok(());
         }
+        self.journal_seq = journal.end();
+        storage.save(self)?;
+        info!("compacted journal into the data file");
+
+        if journal.len() >= COMPACT_EVERY {
+            let mut journal = self.journal.take().context("no save path set.")?;
+            let res = journal.rotate(self);
+            if res.is_ok() {
+                self.journal_seq = journal.end();
+            }
+            self.journal = Some(journal);
+            res?;
+            // include the new journal's snapshot, so it isn't replayed
+            let storage = self.storage.as_ref().context("no save path set.")?;
+            storage.save(self)?;
+            info!("rotated journal");
+        }
+        Ok(())
+    }
+
+    /// Loads the data file at `path`, or starts empty if it doesn't exist
+    /// yet. A new database takes over the JSON save file next to it, if any.
+    pub fn open(backend: Backend, path: PathBuf) -> Result<SaveData> {
+        let _lock = SaveData::lock(&path)?;
+        let storage = backend.open(&path)?;
+        let data = match storage.load()? {
+            Some(data) => data,
+            None if backend == Backend::Sqlite => {
+                let json = path.with_file_name(Backend::Json.default_file());
+                migrate_json(&*storage, &json)?.unwrap_or_else(SaveData::new)
+            }
+            None => SaveData::new(),
+        };
+        data.attach(storage, &path)
+    }
+
+    /// Connects to the daemon that owns the data file at `path`, if one is
+    /// running. The data is then read from and saved through the daemon.
+    pub fn connect(backend: Backend, path: &Path) -> Result<Option<SaveData>> {
+        let Some(mut client) = Client::connect(path) else {
+            return Ok(None);
+        };
+        let state: Tracked =
+            serde_json::from_value(client.call("state", serde_json::Value::Null)?)?;
+        let mut data = SaveData::new();
+        data.logs = state.logs.clone();
+        data.past_logs = state.past_logs.clone();
+        data.tags = state.tags.clone();
+        data.save_path = Some(path.to_string_lossy().to_string());
+        data.seen = data.fingerprint()?;
+        data.daemon = Some(Remote {
+            client,
+            backend,
+            synced: state,
+        });
+        Ok(Some(data))
+    }
+
+    /// Like [`SaveData::open`], but falls back to the newest backup that
+    /// parses when the save file can't be parsed. The broken file is moved
+    /// aside and the backup used is returned alongside the data. Any other
+    /// error is returned as is.
+    pub fn open_or_recover(
+        backend: Backend,
+        path: PathBuf,
+    ) -> Result<(SaveData, Option<PathBuf>)> {
+        let err = match SaveData::open(backend, path.clone()) {
+            Err(e) => e,
+            data => return data.map(|d| (d, None)),
+        };
+        if !storage::is_corrupt(&err) {
+            return Err(err);
+        }
+        warn!("failed to load {}: {err:#}", path.display());
+
+        for backup in list_backups(&path)?.into_iter().rev() {
+            let Some(Some(data)) = backend
+                .open(&backup)
+                .and_then(|s| s.load())
+                .inspect_err(|e| warn!("backup {} is unusable: {e:#}", backup.display()))
+                .ok()
+            else {
+                continue;
+            };
+
+            let corrupt = format!("{}.corrupt-{}", path.display(), timestamp());
+            fs::rename(&path, &corrupt)?;
+            let storage = backend.open(&path)?;
+            storage.save(&data)?;
+            let data = data.attach(storage, &path)?;
+            info!(
+                "recovered from {}, moved broken file to {corrupt}",
+                backup.display()
+            );
+            return Ok((data, Some(backup)));
+        }
+
+        Err(err.context("save file is corrupt and no valid backup was found"))
+    }
+
+    /// Copies the current save file to a timestamped backup next to it and
+    /// removes the oldest backups beyond `keep`.
+    pub fn backup(&self, keep: usize) -> Result<()> {
+        let Some(path) = &self.save_path else {
+            anyhow::bail!("no save path set.");
+        };
+        let path = Path::new(path);
+        if keep == 0 || !fs::exists(path)? {
+            return Ok(());
+        }
+
+        let dest = format!("{}.{}.{BACKUP_EXT}", path.display(), timestamp());
+        fs::copy(path, &dest).with_context(|| format!("failed to back up to {dest}"))?;
+
+        let backups = list_backups(path)?;
+        let excess = backups.len().saturating_sub(keep);
+        for old in &backups[..excess] {
+            fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+
+    /// Brings the data up to date with the journal next to the data file at
+    /// `path`, rewriting the data file if a new journal had to be started.
+    fn open_journal(&mut self, storage: &dyn Storage, path: &Path) -> Result<Journal> {
+        let seq = self.journal_seq;
+        let journal = Journal::open(Journal::path_for(path), self)?;
+        if self.journal_seq != seq {
+            storage.save(self)?;
+        }
+        Ok(journal)
+    }
+
+    /// Makes `storage` at `path` the place this data is saved to, bringing
+    /// it up to date with the journal next to it.
+    fn attach(mut self, storage: Box<dyn Storage>, path: &Path) -> Result<SaveData> {
+        let journal = self.open_journal(&*storage, path)?;
+
+        let SaveData {
+            logs,
+            past_logs,
+            tags,
+            ..
+        } = &mut self;
+        if tags.rebuild_refs(logs.iter().chain(past_logs.iter())) {
+            warn!(
+                "tag refs in {} were out of sync and have been rebuilt",
+                path.display()
+            );
+        }
+        self.save_path = Some(path.to_string_lossy().to_string());
+        self.storage = Some(storage);
+        self.journal = Some(journal);
+        self.seen = self.fingerprint()?;
+        Ok(self)
+    }
+}
+
+/// Copies the JSON save file at `json`, with its journal replayed, into the
+/// empty database `storage` and renames both files to `*.migrated`.
+/// Returns the imported data, or `None` when there is nothing to migrate.
+fn migrate_json(storage: &dyn Storage, json: &Path) -> Result<Option<SaveData>> {
+    if !fs::exists(json)? {
+        return Ok(None);
+    }
+    let mut data = SaveData::open(Backend::Json, json.to_path_buf())?;
+    data.storage = None;
+    data.journal = None;
+    data.journal_seq = 0;
+    storage.save(&data)?;
+
+    for file in [json.to_path_buf(), Journal::path_for(json)] {
+        if fs::exists(&file)? {
+            fs::rename(&file, format!("{}.migrated", file.display()))?;
+        }
+    }
+    info!("migrated {} to the database", json.display());
+    Ok(Some(data))
+}
+
+pub fn default_path(backend: Backend) -> Result<PathBuf> {
+    let Some(mut path) = config_dir() else {
+        anyhow::bail!("could not determine config directory.");
+    };
+    path.push("kairotui");
+    fs::create_dir_all(&path)?;
+    path.push(backend.default_file());
+    Ok(path)
+}
+
+fn timestamp() -> String {
+    Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+/// Backups of `path`, oldest first.
+pub fn list_backups(path: &Path) -> Result<Vec<PathBuf>> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Ok(vec![]);
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let suffix = format!(".{BACKUP_EXT}");
+
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .map(|n| n.to_string_lossy())
+                .is_some_and(|n| n.starts_with(&prefix) && n.ends_with(&suffix))
+        })
+        .collect();
+    backups.sort();
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn database_takes_over_json_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join(Backend::Json.default_file());
+        let mut data = SaveData::open(Backend::Json, json.clone()).unwrap();
+        data.logs
+            .push(Log::new("a".to_string(), HashSet::from(["t".to_string()])));
+        data.tags.add("t").refs += 1;
+        data.save().unwrap();
+        // only in the journal until the data file is compacted
+        data.past_logs
+            .push(Log::new("b".to_string(), HashSet::new()));
+        data.save().unwrap();
+
+        let db = dir.path().join(Backend::Sqlite.default_file());
+        let migrated = SaveData::open(Backend::Sqlite, db.clone()).unwrap();
+        assert_eq!(migrated.logs, data.logs);
+        assert_eq!(migrated.past_logs, data.past_logs);
+        assert_eq!(migrated.tags, data.tags);
+        assert!(!fs::exists(&json).unwrap());
+        assert!(fs::exists(dir.path().join("save.dat.migrated")).unwrap());
+
+        drop(migrated);
+        let reopened = SaveData::open(Backend::Sqlite, db).unwrap();
+        assert_eq!(reopened.logs, data.logs);
+    }
+
+    #[test]
+    fn journal_is_rotated_once_compacted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(Backend::Json.default_file());
+        let mut data = SaveData::open(Backend::Json, path.clone()).unwrap();
+        for i in 0..COMPACT_EVERY + 10 {
+            data.logs.push(Log::new(i.to_string(), HashSet::new()));
+            data.save().unwrap();
+        }
+
+        let journal = Journal::path_for(&path);
+        assert!(fs::exists(format!("{}.0", journal.display())).unwrap());
+        let current = fs::read_to_string(&journal).unwrap();
+        assert_eq!(current.lines().count(), 11);
+        // the first snapshot, every log added and the rotation snapshot
+        let events = journal::read(&journal).unwrap();
+        assert_eq!(events.len() as u64, COMPACT_EVERY + 12);
+
+        let reopened = SaveData::open(Backend::Json, path).unwrap();
+        assert_eq!(reopened.logs, data.logs);
+        assert_eq!(reopened.journal_seq, data.journal_seq);
+    }
+
+    #[test]
+    fn broken_journal_leaves_data_file_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(Backend::Json.default_file());
+        let mut data = SaveData::open(Backend::Json, path.clone()).unwrap();
+        data.logs.push(Log::new("a".to_string(), HashSet::new()));
+        data.save().unwrap();
+        data.backup(DEFAULT_BACKUPS).unwrap();
+        data.compact().unwrap();
+        drop(data);
+
+        let journal = Journal::path_for(&path);
+        let mut lines = fs::read_to_string(&journal).unwrap();
+        lines.insert_str(0, "garbage\n");
+        fs::write(&journal, lines).unwrap();
+        assert!(SaveData::open_or_recover(Backend::Json, path.clone()).is_err());
+        assert!(fs::exists(&path).unwrap());
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(!names.iter().any(|n| n.contains("corrupt")));
+    }
+
+    #[test]
+    fn read_only_data_reloads_but_never_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(Backend::Json.default_file());
+        let mut writer = SaveData::open(Backend::Json, path.clone()).unwrap();
+        let mut reader = SaveData::open(Backend::Json, path.clone()).unwrap();
+        reader.read_only = Some("test".to_string());
+
+        writer.logs.push(Log::new("a".to_string(), HashSet::new()));
+        writer.save().unwrap();
+        assert!(reader.changed_on_disk());
+        assert!(reader.save().unwrap());
+        assert_eq!(reader.logs, writer.logs);
+
+        reader.logs.clear();
+        assert!(!reader.save().unwrap());
+        let reopened = SaveData::open(Backend::Json, path).unwrap();
+        assert_eq!(reopened.logs, writer.logs);
     }
 }
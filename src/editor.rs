@@ -0,0 +1,387 @@
+use ratatui::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    text::Span,
+};
+
+/// What an editing key does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edit {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Start,
+    End,
+    DeleteBefore,
+    DeleteAfter,
+    DeleteWordBefore,
+    DeleteWordAfter,
+    DeleteToStart,
+    DeleteToEnd,
+}
+
+impl Edit {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Edit::Left => "move cursor",
+            Edit::Right => "move cursor",
+            Edit::WordLeft => "move by word",
+            Edit::WordRight => "move by word",
+            Edit::Start => "start / end of line",
+            Edit::End => "start / end of line",
+            Edit::DeleteBefore => "delete char",
+            Edit::DeleteAfter => "delete char",
+            Edit::DeleteWordBefore => "delete word before cursor",
+            Edit::DeleteWordAfter => "delete word after cursor",
+            Edit::DeleteToStart => "delete to start of line",
+            Edit::DeleteToEnd => "delete to end of line",
+        }
+    }
+}
+
+const NONE: KeyModifiers = KeyModifiers::NONE;
+const CTRL: KeyModifiers = KeyModifiers::CONTROL;
+const ALT: KeyModifiers = KeyModifiers::ALT;
+
+/// Keys of [`LineEditor::handle_key`], also listed in the help overlay.
+/// Other characters typed without ctrl or alt are inserted.
+pub const BINDINGS: [(KeyCode, KeyModifiers, Edit); 24] = [
+    (KeyCode::Left, NONE, Edit::Left),
+    (KeyCode::Char('b'), CTRL, Edit::Left),
+    (KeyCode::Right, NONE, Edit::Right),
+    (KeyCode::Char('f'), CTRL, Edit::Right),
+    (KeyCode::Left, CTRL, Edit::WordLeft),
+    (KeyCode::Left, ALT, Edit::WordLeft),
+    (KeyCode::Char('b'), ALT, Edit::WordLeft),
+    (KeyCode::Right, CTRL, Edit::WordRight),
+    (KeyCode::Right, ALT, Edit::WordRight),
+    (KeyCode::Char('f'), ALT, Edit::WordRight),
+    (KeyCode::Home, NONE, Edit::Start),
+    (KeyCode::Char('a'), CTRL, Edit::Start),
+    (KeyCode::End, NONE, Edit::End),
+    (KeyCode::Char('e'), CTRL, Edit::End),
+    (KeyCode::Backspace, NONE, Edit::DeleteBefore),
+    (KeyCode::Char('h'), CTRL, Edit::DeleteBefore),
+    (KeyCode::Delete, NONE, Edit::DeleteAfter),
+    (KeyCode::Char('d'), CTRL, Edit::DeleteAfter),
+    (KeyCode::Backspace, CTRL, Edit::DeleteWordBefore),
+    (KeyCode::Backspace, ALT, Edit::DeleteWordBefore),
+    (KeyCode::Char('w'), CTRL, Edit::DeleteWordBefore),
+    (KeyCode::Char('d'), ALT, Edit::DeleteWordAfter),
+    (KeyCode::Char('u'), CTRL, Edit::DeleteToStart),
+    (KeyCode::Char('k'), CTRL, Edit::DeleteToEnd),
+];
+
+/// Single-line text editor backing the input dialog. The cursor is a char
+/// index into `text`, so multi-byte input is never split.
+#[derive(Debug, Default, Clone)]
+pub struct LineEditor {
+    text: String,
+    cursor: usize,
+}
+
+impl From<&str> for LineEditor {
+    /// Starts with `text`, the cursor at its end.
+    fn from(text: &str) -> Self {
+        let mut editor = LineEditor::default();
+        editor.insert_str(text);
+        editor
+    }
+}
+
+impl LineEditor {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.cursor = 0;
+    }
+
+    /// Takes the current text, leaving the editor empty.
+    pub fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.text)
+    }
+
+    pub fn before_cursor(&self) -> &str {
+        &self.text[..self.byte_idx(self.cursor)]
+    }
+
+    /// Replaces the `n` chars before the cursor with `s`.
+    pub fn replace_before_cursor(&mut self, n: usize, s: &str) {
+        self.delete_range(self.cursor.saturating_sub(n), self.cursor);
+        self.insert_str(s);
+    }
+
+    /// Terminal columns between the start of the text and the cursor.
+    pub fn cursor_width(&self) -> usize {
+        Span::raw(self.before_cursor()).width()
+    }
+
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn byte_idx(&self, char_idx: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(char_idx)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn char_at(&self, char_idx: usize) -> Option<char> {
+        self.text.chars().nth(char_idx)
+    }
+
+    /// Inserts `s` at the cursor; newlines are flattened to spaces.
+    pub fn insert_str(&mut self, s: &str) {
+        let s: String = s
+            .chars()
+            .map(|c| {
+                if c == '\n' || c == '\r' || c == '\t' {
+                    ' '
+                } else {
+                    c
+                }
+            })
+            .collect();
+        let idx = self.byte_idx(self.cursor);
+        self.text.insert_str(idx, &s);
+        self.cursor += s.chars().count();
+    }
+
+    fn word_start(&self) -> usize {
+        let mut i = self.cursor;
+        while i > 0 && self.char_at(i - 1).is_some_and(char::is_whitespace) {
+            i -= 1;
+        }
+        while i > 0 && self.char_at(i - 1).is_some_and(|c| !c.is_whitespace()) {
+            i -= 1;
+        }
+        i
+    }
+
+    fn word_end(&self) -> usize {
+        let len = self.len();
+        let mut i = self.cursor;
+        while i < len && self.char_at(i).is_some_and(char::is_whitespace) {
+            i += 1;
+        }
+        while i < len && self.char_at(i).is_some_and(|c| !c.is_whitespace()) {
+            i += 1;
+        }
+        i
+    }
+
+    /// Removes the chars in `[from, to)` and leaves the cursor at `from`.
+    fn delete_range(&mut self, from: usize, to: usize) {
+        let (a, b) = (self.byte_idx(from), self.byte_idx(to));
+        self.text.replace_range(a..b, "");
+        self.cursor = from;
+    }
+
+    /// Applies an editing key. Returns `false` for keys the editor ignores.
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        // shift only selects the case of typed chars
+        let modifiers = key.modifiers - KeyModifiers::SHIFT;
+        let bound = BINDINGS
+            .iter()
+            .find(|(code, m, _)| *code == key.code && *m == modifiers);
+        let Some((_, _, edit)) = bound else {
+            return match key.code {
+                KeyCode::Char(c) if !modifiers.intersects(CTRL | ALT) => {
+                    self.insert_str(c.encode_utf8(&mut [0; 4]));
+                    true
+                }
+                _ => false,
+            };
+        };
+
+        let len = self.len();
+        match edit {
+            Edit::Left => self.cursor = self.cursor.saturating_sub(1),
+            Edit::Right => self.cursor = (self.cursor + 1).min(len),
+            Edit::WordLeft => self.cursor = self.word_start(),
+            Edit::WordRight => self.cursor = self.word_end(),
+            Edit::Start => self.cursor = 0,
+            Edit::End => self.cursor = len,
+            Edit::DeleteBefore => {
+                if self.cursor > 0 {
+                    self.delete_range(self.cursor - 1, self.cursor);
+                }
+            }
+            Edit::DeleteAfter => {
+                if self.cursor < len {
+                    self.delete_range(self.cursor, self.cursor + 1);
+                }
+            }
+            Edit::DeleteWordBefore => self.delete_range(self.word_start(), self.cursor),
+            Edit::DeleteWordAfter => self.delete_range(self.cursor, self.word_end()),
+            Edit::DeleteToStart => self.delete_range(0, self.cursor),
+            Edit::DeleteToEnd => self.delete_range(self.cursor, len),
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(editor: &mut LineEditor, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        editor.handle_key(KeyEvent::new(code, modifiers))
+    }
+
+    fn type_str(editor: &mut LineEditor, s: &str) {
+        for c in s.chars() {
+            assert!(press(editor, KeyCode::Char(c), NONE));
+        }
+    }
+
+    #[test]
+    fn cursor_counts_chars_not_bytes() {
+        let mut editor = LineEditor::default();
+        type_str(&mut editor, "héllo wörld");
+        assert_eq!(editor.cursor, 11);
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Left, NONE);
+        }
+        assert_eq!(editor.before_cursor(), "héllo w");
+        press(&mut editor, KeyCode::Backspace, NONE);
+        press(&mut editor, KeyCode::Delete, NONE);
+        type_str(&mut editor, "ø");
+        assert_eq!(editor.text(), "héllo ørld");
+        assert_eq!(editor.before_cursor(), "héllo ø");
+    }
+
+    #[test]
+    fn cursor_width_counts_wide_chars() {
+        let mut editor = LineEditor::from("日本 x");
+        assert_eq!(editor.cursor_width(), 6);
+        press(&mut editor, KeyCode::Home, NONE);
+        press(&mut editor, KeyCode::Right, NONE);
+        assert_eq!(editor.cursor_width(), 2);
+    }
+
+    #[test]
+    fn cursor_stops_at_the_edges() {
+        let mut editor = LineEditor::from("ab");
+        press(&mut editor, KeyCode::Right, NONE);
+        assert_eq!(editor.cursor, 2);
+        press(&mut editor, KeyCode::Delete, NONE);
+        assert_eq!(editor.text(), "ab");
+
+        press(&mut editor, KeyCode::Home, NONE);
+        press(&mut editor, KeyCode::Left, NONE);
+        assert_eq!(editor.cursor, 0);
+        press(&mut editor, KeyCode::Backspace, NONE);
+        press(&mut editor, KeyCode::Char('w'), CTRL);
+        press(&mut editor, KeyCode::Char('u'), CTRL);
+        assert_eq!(editor.text(), "ab");
+
+        let mut empty = LineEditor::default();
+        for (code, modifiers, _) in BINDINGS {
+            assert!(press(&mut empty, code, modifiers));
+            assert_eq!((empty.text(), empty.cursor), ("", 0));
+        }
+    }
+
+    #[test]
+    fn moves_by_word() {
+        let mut editor = LineEditor::from("fix  the  bug");
+        press(&mut editor, KeyCode::Left, CTRL);
+        assert_eq!(editor.before_cursor(), "fix  the  ");
+        press(&mut editor, KeyCode::Char('b'), ALT);
+        assert_eq!(editor.before_cursor(), "fix  ");
+        press(&mut editor, KeyCode::Right, ALT);
+        assert_eq!(editor.before_cursor(), "fix  the");
+        press(&mut editor, KeyCode::Char('f'), ALT);
+        assert_eq!(editor.before_cursor(), "fix  the  bug");
+    }
+
+    #[test]
+    fn deletes_words_around_the_cursor() {
+        let mut editor = LineEditor::from("über  café  bar");
+        press(&mut editor, KeyCode::Char('w'), CTRL);
+        assert_eq!(editor.text(), "über  café  ");
+        press(&mut editor, KeyCode::Backspace, ALT);
+        assert_eq!(editor.text(), "über  ");
+
+        let mut editor = LineEditor::from("über  café  bar");
+        press(&mut editor, KeyCode::Home, NONE);
+        press(&mut editor, KeyCode::Char('d'), ALT);
+        assert_eq!(editor.text(), "  café  bar");
+        press(&mut editor, KeyCode::Char('d'), ALT);
+        assert_eq!(editor.text(), "  bar");
+        assert_eq!(editor.cursor, 0);
+    }
+
+    #[test]
+    fn deletes_to_either_end() {
+        let mut editor = LineEditor::from("één twee");
+        for _ in 0..4 {
+            press(&mut editor, KeyCode::Char('b'), CTRL);
+        }
+        press(&mut editor, KeyCode::Char('k'), CTRL);
+        assert_eq!(editor.text(), "één ");
+        press(&mut editor, KeyCode::Char('a'), CTRL);
+        press(&mut editor, KeyCode::Char('f'), CTRL);
+        press(&mut editor, KeyCode::Char('u'), CTRL);
+        assert_eq!(editor.text(), "én ");
+        assert_eq!(editor.cursor, 0);
+    }
+
+    #[test]
+    fn every_binding_is_handled_once() {
+        for (i, (code, modifiers, edit)) in BINDINGS.iter().enumerate() {
+            assert!(
+                !BINDINGS[i + 1..]
+                    .iter()
+                    .any(|(c, m, _)| c == code && m == modifiers),
+                "{code:?} {modifiers:?} is bound twice"
+            );
+            assert!(!edit.describe().is_empty());
+            let mut editor = LineEditor::from("one two");
+            press(&mut editor, KeyCode::Left, NONE);
+            assert!(press(&mut editor, *code, *modifiers), "{edit:?}");
+        }
+    }
+
+    #[test]
+    fn types_shifted_chars_and_ignores_unbound_keys() {
+        let mut editor = LineEditor::default();
+        assert!(press(&mut editor, KeyCode::Char('A'), KeyModifiers::SHIFT));
+        assert!(!press(&mut editor, KeyCode::Char('x'), CTRL));
+        assert!(!press(&mut editor, KeyCode::Char('x'), ALT));
+        assert!(!press(&mut editor, KeyCode::Up, NONE));
+        assert_eq!(editor.text(), "A");
+    }
+
+    #[test]
+    fn pasted_newlines_become_spaces() {
+        let mut editor = LineEditor::default();
+        editor.insert_str("a\nb\r\tc");
+        assert_eq!(editor.text(), "a b  c");
+        assert_eq!(editor.cursor, 6);
+    }
+
+    #[test]
+    fn replaces_chars_before_the_cursor() {
+        let mut editor = LineEditor::from("tag:fö rest");
+        for _ in 0..5 {
+            press(&mut editor, KeyCode::Left, NONE);
+        }
+        editor.replace_before_cursor(2, "foo");
+        assert_eq!(editor.text(), "tag:foo rest");
+        assert_eq!(editor.before_cursor(), "tag:foo");
+        assert_eq!(editor.take(), "tag:foo rest");
+        assert!(editor.is_empty());
+        assert_eq!(editor.cursor, 0);
+    }
+}
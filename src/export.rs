@@ -0,0 +1,187 @@
+use crate::{
+    data::SaveData,
+    log::{Log, duration_as_hhmmss},
+    report::start_of_day,
+    tag,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Days, Local, NaiveDate, SecondsFormat};
+use clap::ValueEnum;
+use regex::Regex;
+use serde_json::json;
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Jsonl,
+    #[value(alias = "md")]
+    Markdown,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Format> {
+        Format::from_str(s, true).map_err(|_| anyhow::anyhow!("unknown format '{s}'"))
+    }
+}
+
+/// Restricts exported logs to a date range and/or a set of tags.
+#[derive(Debug, Default, Clone)]
+pub struct Filter {
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+    pub tags: Vec<String>,
+}
+
+impl Filter {
+    /// `from` and `to` are inclusive calendar days.
+    pub fn new(
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+        tags: Vec<String>,
+    ) -> Self {
+        Self {
+            from: from.map(start_of_day),
+            to: to.map(|d| start_of_day(d.checked_add_days(Days::new(1)).unwrap_or(d))),
+            tags: tags.iter().map(|t| tag::normalize(t)).collect(),
+        }
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        let in_range = self.from.is_none_or(|from| log.end >= from)
+            && self.to.is_none_or(|to| log.start < to);
+        // a parent tag also matches all of its descendants
+        let has_tag = self.tags.is_empty()
+            || self
+                .tags
+                .iter()
+                .any(|f| log.tags.iter().any(|t| tag::is_under(t, f)));
+        in_range && has_tag
+    }
+}
+
+pub fn parse_date(s: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .with_context(|| format!("invalid date '{s}', expected YYYY-MM-DD"))
+}
+
+/// Parses the in-app export dialog input:
+/// `<format> <path> (from: <date>)? (to: <date>)? (tag: <name>)*`
+pub fn parse_action(input: &str) -> Result<(Format, PathBuf, Filter)> {
+    let date_re = Regex::new(r"(from|to):\s(\S+)").unwrap();
+    let mut from = None;
+    let mut to = None;
+    for cap in date_re.captures_iter(input) {
+        let date = Some(parse_date(&cap[2])?);
+        match &cap[1] {
+            "from" => from = date,
+            _ => to = date,
+        }
+    }
+    let rest = date_re.replace_all(input, "").to_string();
+    let (rest, tags) = crate::log::parse_input(rest);
+
+    let mut parts = rest.split_whitespace();
+    let (Some(format), Some(path)) = (parts.next(), parts.next()) else {
+        anyhow::bail!("expected <format> <path>");
+    };
+    let path = crate::config::expand_path(path)?;
+
+    Ok((Format::parse(format)?, path, Filter::new(from, to, tags)))
+}
+
+/// Logs from both the active and past lists that pass `filter`, oldest first.
+pub fn collect<'a>(data: &'a SaveData, filter: &Filter) -> Vec<&'a Log> {
+    let mut logs: Vec<&Log> = data
+        .past_logs
+        .iter()
+        .chain(data.logs.iter())
+        .filter(|l| filter.matches(l))
+        .collect();
+    logs.sort_by_key(|l| l.start);
+    logs
+}
+
+fn sorted_tags(log: &Log) -> Vec<&str> {
+    let mut tags: Vec<&str> = log.tags.iter().map(|t| t.as_str()).collect();
+    tags.sort();
+    tags
+}
+
+fn rfc3339(date: &DateTime<Local>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+fn md_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+pub fn write(
+    out: &mut impl Write,
+    data: &SaveData,
+    format: Format,
+    filter: &Filter,
+) -> Result<usize> {
+    let logs = collect(data, filter);
+
+    match format {
+        Format::Csv => {
+            let mut csv = csv::Writer::from_writer(out);
+            csv.write_record(["name", "tags", "start", "end", "duration"])?;
+            for l in &logs {
+                csv.write_record([
+                    l.name.as_str(),
+                    &sorted_tags(l).join(";"),
+                    &rfc3339(&l.start),
+                    &rfc3339(&l.end),
+                    &duration_as_hhmmss(l.duration()),
+                ])?;
+            }
+            csv.flush()?;
+        }
+        Format::Jsonl => {
+            for l in &logs {
+                let line = json!({
+                    "name": l.name,
+                    "tags": sorted_tags(l),
+                    "start": rfc3339(&l.start),
+                    "end": rfc3339(&l.end),
+                    "duration_seconds": l.duration().num_seconds(),
+                    "done": l.done,
+                });
+                writeln!(out, "{line}")?;
+            }
+        }
+        Format::Markdown => {
+            writeln!(out, "| Name | Tags | Start | End | Duration |")?;
+            writeln!(out, "| --- | --- | --- | --- | ---: |")?;
+            for l in &logs {
+                writeln!(
+                    out,
+                    "| {} | {} | {} | {} | {} |",
+                    md_cell(&l.name),
+                    md_cell(&sorted_tags(l).join(", ")),
+                    l.start.format("%Y-%m-%d %H:%M"),
+                    l.end.format("%Y-%m-%d %H:%M"),
+                    duration_as_hhmmss(l.duration()),
+                )?;
+            }
+        }
+    }
+    Ok(logs.len())
+}
+
+pub fn write_file(
+    path: &Path,
+    data: &SaveData,
+    format: Format,
+    filter: &Filter,
+) -> Result<usize> {
+    let mut file = File::create(path)
+        .with_context(|| format!("failed to create {}", path.display()))?;
+    write(&mut file, data, format, filter)
+}
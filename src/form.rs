@@ -0,0 +1,120 @@
+use crate::{editor::LineEditor, theme};
+use ratatui::{
+    Frame,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{Constraint, Flex, Layout, Position},
+    style::Stylize,
+    text::{Line, Span, ToSpan},
+    widgets::{Block, BorderType, Clear, Paragraph, Widget},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormResult {
+    Submit,
+    Cancel,
+}
+
+/// A dialog of labelled single-line fields. Tab/Up/Down move between
+/// fields, Enter submits and Esc cancels.
+#[derive(Debug, Clone)]
+pub struct Form {
+    pub title: &'static str,
+    fields: Vec<(&'static str, LineEditor)>,
+    focus: usize,
+    /// Shown below the fields, e.g. when validation fails.
+    pub error: Option<String>,
+}
+
+impl Form {
+    pub fn new(title: &'static str, fields: Vec<(&'static str, String)>) -> Form {
+        Form {
+            title,
+            fields: fields
+                .into_iter()
+                .map(|(label, value)| (label, LineEditor::from(value.as_str())))
+                .collect(),
+            focus: 0,
+            error: None,
+        }
+    }
+
+    pub fn value(&self, label: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(l, _)| *l == label)
+            .map(|(_, e)| e.text())
+    }
+
+    pub fn insert_str(&mut self, s: &str) {
+        self.fields[self.focus].1.insert_str(s);
+    }
+
+    /// Returns the result once `key` submits or cancels the form.
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<FormResult> {
+        let len = self.fields.len();
+        match key.code {
+            KeyCode::Enter => return Some(FormResult::Submit),
+            KeyCode::Esc => return Some(FormResult::Cancel),
+            KeyCode::Tab | KeyCode::Down => self.focus = (self.focus + 1) % len,
+            KeyCode::BackTab | KeyCode::Up => self.focus = (self.focus + len - 1) % len,
+            _ => {
+                self.fields[self.focus].1.handle_key(key);
+            }
+        }
+        None
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let label_width = self.fields.iter().map(|f| f.0.len()).max().unwrap_or(0);
+        let height = self.fields.len() as u16 + 2 + self.error.is_some() as u16 * 2;
+        let area = {
+            let vert = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center);
+            let horz =
+                Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
+            let [area] = vert.areas(frame.area());
+            let [area] = horz.areas(area);
+            area
+        };
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .fg(theme::orange())
+            .bg(theme::bg0())
+            .title(self.title.to_span().into_centered_line());
+        let inner = block.inner(area);
+
+        let mut lines: Vec<Line> = self
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(i, (label, editor))| {
+                let color = if i == self.focus {
+                    theme::orange()
+                } else {
+                    theme::text_alt()
+                };
+                Line::from(vec![
+                    Span::styled(format!("{label:>label_width$}: "), color),
+                    Span::styled(editor.text().to_string(), theme::text()),
+                ])
+            })
+            .collect();
+        if let Some(e) = &self.error {
+            lines.push(Line::default());
+            lines.push(Line::from(e.clone()).fg(theme::red()));
+        }
+
+        frame.render_widget(Clear, area);
+        Paragraph::new(lines)
+            .block(block)
+            .render(area, frame.buffer_mut());
+
+        let x = inner.x
+            + label_width as u16
+            + 2
+            + self.fields[self.focus].1.cursor_width() as u16;
+        frame.set_cursor_position(Position::new(
+            x.min(inner.right().saturating_sub(1)),
+            inner.y + self.focus as u16,
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{
+    GLOBAL_ACTIONS, INPUT_KEYS, State, editor,
+    keymap::{self, Action},
+    theme,
+};
+use ratatui::{
+    Frame,
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    layout::{Constraint, Flex, Layout, Rect},
+    style::Stylize,
+    text::{Line, Span, ToSpan},
+    widgets::{Block, BorderType, Clear, Paragraph, Widget},
+};
+
+/// Item navigation is left out of the hint bar to keep it on one line.
+const NAVIGATION: [Action; 4] = [
+    Action::NextItem,
+    Action::PrevItem,
+    Action::FirstItem,
+    Action::LastItem,
+];
+
+fn section(title: &str, rows: Vec<(String, &str)>, lines: &mut Vec<Line<'static>>) {
+    let width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0);
+    lines.push(Line::from(title.to_string()).fg(theme::blue()).bold());
+    for (keys, desc) in rows {
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {keys:<width$}  "), theme::orange()),
+            Span::styled(desc.to_string(), theme::text()),
+        ]));
+    }
+    lines.push(Line::default());
+}
+
+/// One row per description of `bindings`, with all keys that have it.
+fn rows<T>(
+    bindings: impl IntoIterator<Item = (KeyCode, KeyModifiers, T)>,
+    describe: impl Fn(T) -> &'static str,
+) -> Vec<(String, &'static str)> {
+    let mut rows: Vec<(String, &'static str)> = vec![];
+    for (code, modifiers, effect) in bindings {
+        let key = keymap::format_sequence(&[KeyEvent::new(code, modifiers)]);
+        let desc = describe(effect);
+        match rows.iter_mut().find(|(_, d)| *d == desc) {
+            Some((keys, _)) => *keys = format!("{keys}, {key}"),
+            None => rows.push((key, desc)),
+        }
+    }
+    rows
+}
+
+/// Lists every binding of the focused tab, the global actions and the
+/// input dialog keys.
+pub fn render(frame: &mut Frame, state: &State) {
+    let keymap = &state.config.keymap;
+    let bound = |actions: &[(Action, &'static str)]| {
+        actions
+            .iter()
+            .map(|(a, desc)| (keymap.keys_for(*a).join(", "), *desc))
+            .filter(|(keys, _)| !keys.is_empty())
+            .collect::<Vec<_>>()
+    };
+
+    let tab = &state.rendered_lists[state.focused_list_idx];
+    let mut lines = vec![];
+    section(tab.get_title(), bound(tab.actions()), &mut lines);
+    section("Global", bound(&GLOBAL_ACTIONS), &mut lines);
+    let dialog = INPUT_KEYS.map(|(code, k)| (code, KeyModifiers::NONE, k));
+    let mut input = rows(dialog, |k| k.describe());
+    input.extend(rows(editor::BINDINGS, |e| e.describe()));
+    section("Input dialog", input, &mut lines);
+    lines.pop();
+
+    let width = lines.iter().map(Line::width).max().unwrap_or(0) as u16 + 4;
+    let height = lines.len() as u16 + 2;
+    let area = {
+        let vert = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center);
+        let horz = Layout::horizontal([Constraint::Length(width)]).flex(Flex::Center);
+        let [area] = vert.areas(frame.area());
+        let [area] = horz.areas(area);
+        area
+    };
+
+    frame.render_widget(Clear, area);
+    Paragraph::new(lines)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .fg(theme::orange())
+                .bg(theme::bg0())
+                .title(" Help ".to_span().into_centered_line()),
+        )
+        .render(area, frame.buffer_mut());
+}
+
+/// One-line summary of the main actions at the bottom of the screen.
+pub fn render_hint_bar(area: &Rect, frame: &mut Frame, state: &State) {
+    let tab = &state.rendered_lists[state.focused_list_idx];
+    let actions = tab
+        .actions()
+        .iter()
+        .filter(|(a, _)| !NAVIGATION.contains(a))
+        .chain(GLOBAL_ACTIONS.iter());
+
+    let mut spans = vec![];
+    for (action, desc) in actions {
+        let Some(key) = state.config.keymap.keys_for(*action).into_iter().next() else {
+            continue;
+        };
+        spans.push(Span::styled(format!(" {key} "), theme::orange()).bold());
+        spans.push(Span::styled(format!("{desc} "), theme::text_alt()));
+    }
+    Paragraph::new(Line::from(spans))
+        .bg(theme::bg0())
+        .render(*area, frame.buffer_mut());
+}
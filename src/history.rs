@@ -0,0 +1,107 @@
+use crate::{
+    data::SaveData,
+    journal::{self, Change},
+    log::Log,
+    tag::TagSys,
+};
+use std::collections::VecDeque;
+use tracing::warn;
+
+/// Oldest changes are forgotten once the undo stack holds this many.
+pub const MAX_UNDO: usize = 100;
+
+/// The parts of [`SaveData`] that user actions change, as they were before
+/// an action. Only kept until the action is recorded in the [`History`].
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    logs: Vec<Log>,
+    past_logs: Vec<Log>,
+    tags: TagSys,
+}
+
+impl Snapshot {
+    pub fn take(data: &SaveData) -> Snapshot {
+        Snapshot {
+            logs: data.logs.clone(),
+            past_logs: data.past_logs.clone(),
+            tags: data.tags.clone(),
+        }
+    }
+}
+
+/// A recorded change: what turns the data back into its state before the
+/// change, and what turns it into the state after.
+#[derive(Debug)]
+struct Entry {
+    desc: String,
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
+
+fn apply(changes: &[Change], data: &mut SaveData) -> bool {
+    let SaveData {
+        logs,
+        past_logs,
+        tags,
+        ..
+    } = data;
+    for change in changes {
+        if let Err(e) = change.apply(logs, past_logs, tags) {
+            warn!("can't apply undo history: {e:#}");
+            return false;
+        }
+    }
+    tags.rebuild_refs(logs.iter().chain(past_logs.iter()));
+    true
+}
+
+/// Undo and redo stacks of changes, labelled with a description of the
+/// change. Only the logs a change touched are kept.
+#[derive(Debug, Default)]
+pub struct History {
+    undo: VecDeque<Entry>,
+    redo: Vec<Entry>,
+}
+
+impl History {
+    /// Records a change described by `desc` that turned `before` into the
+    /// current `data`.
+    pub fn push(&mut self, desc: String, before: Snapshot, data: &SaveData) {
+        let before = (&before.logs[..], &before.past_logs[..], &before.tags);
+        let after = (&data.logs[..], &data.past_logs[..], &data.tags);
+        let entry = Entry {
+            desc,
+            undo: journal::diff(after, before),
+            redo: journal::diff(before, after),
+        };
+        if self.undo.len() == MAX_UNDO {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(entry);
+        self.redo.clear();
+    }
+
+    /// Reverts the last change and returns its description.
+    pub fn undo(&mut self, data: &mut SaveData) -> Option<String> {
+        let entry = self.undo.pop_back()?;
+        if !apply(&entry.undo, data) {
+            *self = History::default();
+            return None;
+        }
+        let desc = entry.desc.clone();
+        self.redo.push(entry);
+        Some(desc)
+    }
+
+    /// Reapplies the last undone change and returns its description.
+    pub fn redo(&mut self, data: &mut SaveData) -> Option<String> {
+        let entry = self.redo.pop()?;
+        if !apply(&entry.redo, data) {
+            *self = History::default();
+            return None;
+        }
+        let desc = entry.desc.clone();
+        self.undo.push_back(entry);
+        Some(desc)
+    }
+}
@@ -0,0 +1,486 @@
+use crate::{
+    data::SaveData,
+    log::{Log, Segment, new_id},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use clap::ValueEnum;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Source {
+    /// timewarrior `.data` files (or the `data` directory itself)
+    Timewarrior,
+    /// Toggl Track "detailed report" CSV export
+    Toggl,
+    /// ledger timeclock (`i`/`o` lines)
+    Timeclock,
+}
+
+/// A log read from another tracker, before it is merged into `SaveData`.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub name: String,
+    pub tags: HashSet<String>,
+    pub start: DateTime<Local>,
+    pub end: Option<DateTime<Local>>,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub added: usize,
+    pub duplicates: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+/// Tags are restricted to word characters and path separators so they
+/// survive `tag: <name>` parsing.
+fn sanitize_tag(tag: &str) -> String {
+    let tag: String = tag
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == ':' || c == '/' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    crate::tag::normalize(&tag)
+}
+
+fn tag_set<'a>(tags: impl IntoIterator<Item = &'a str>) -> HashSet<String> {
+    tags.into_iter()
+        .map(sanitize_tag)
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn local(naive: NaiveDateTime) -> Result<DateTime<Local>> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .with_context(|| format!("{naive} does not exist in the local timezone"))
+}
+
+pub fn read(source: Source, path: &Path) -> Result<Vec<Entry>> {
+    match source {
+        Source::Timewarrior => {
+            let files: Vec<PathBuf> = if path.is_dir() {
+                let mut files: Vec<PathBuf> = fs::read_dir(path)?
+                    .filter_map(|e| e.ok().map(|e| e.path()))
+                    .filter(|p| p.extension().is_some_and(|e| e == "data"))
+                    .collect();
+                files.sort();
+                files
+            } else {
+                vec![path.to_path_buf()]
+            };
+            let mut entries = vec![];
+            for f in files {
+                let text = fs::read_to_string(&f)
+                    .with_context(|| format!("failed to read {}", f.display()))?;
+                entries.extend(parse_timewarrior(&text)?);
+            }
+            Ok(entries)
+        }
+        Source::Toggl => parse_toggl(fs::File::open(path)?),
+        Source::Timeclock => parse_timeclock(&fs::read_to_string(path)?),
+    }
+}
+
+/// Splits the tag section of a timewarrior line, honouring double quotes.
+fn split_quoted(s: &str) -> Vec<String> {
+    let mut out = vec![];
+    let mut cur = String::new();
+    let mut quoted = false;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if quoted => cur.extend(chars.next()),
+            '"' => quoted = !quoted,
+            ' ' if !quoted => {
+                if !cur.is_empty() {
+                    out.push(std::mem::take(&mut cur));
+                }
+            }
+            _ => cur.push(c),
+        }
+    }
+    if !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+/// `inc 20240101T100000Z - 20240101T113000Z # tag "other tag" # "annotation"`
+pub fn parse_timewarrior(text: &str) -> Result<Vec<Entry>> {
+    let parse_ts = |s: &str| -> Result<DateTime<Local>> {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y%m%dT%H%M%SZ")
+            .with_context(|| format!("invalid timewarrior timestamp '{s}'"))?;
+        Ok(Utc.from_utc_datetime(&naive).with_timezone(&Local))
+    };
+
+    let mut entries = vec![];
+    for line in text.lines() {
+        let line = line.trim();
+        let Some(rest) = line.strip_prefix("inc ") else {
+            continue;
+        };
+        let mut sections = rest.splitn(3, " # ");
+        let range = sections.next().unwrap_or_default();
+        let tags = sections.next().map(split_quoted).unwrap_or_default();
+        let annotation = sections.next().map(|a| split_quoted(a).join(" "));
+
+        let (start, end) = match range.split_once(" - ") {
+            Some((s, e)) => (parse_ts(s.trim())?, Some(parse_ts(e.trim())?)),
+            None => (parse_ts(range.trim())?, None),
+        };
+        // timewarrior allows intervals with neither tags nor annotation
+        let name = annotation
+            .filter(|a| !a.is_empty())
+            .or_else(|| (!tags.is_empty()).then(|| tags.join(" ")))
+            .unwrap_or_else(|| "(untagged)".to_string());
+
+        entries.push(Entry {
+            name,
+            tags: tag_set(tags.iter().map(|t| t.as_str())),
+            start,
+            end,
+        });
+    }
+    Ok(entries)
+}
+
+pub fn parse_toggl(reader: impl std::io::Read) -> Result<Vec<Entry>> {
+    let mut csv = csv::Reader::from_reader(reader);
+    let headers = csv.headers()?.clone();
+    let col = |name: &str| {
+        headers
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .with_context(|| format!("Toggl CSV is missing the '{name}' column"))
+    };
+    let (desc, project, tags) =
+        (col("Description")?, col("Project").ok(), col("Tags").ok());
+    let (start_date, start_time) = (col("Start date")?, col("Start time")?);
+    let (end_date, end_time) = (col("End date")?, col("End time")?);
+
+    let parse_ts = |date: &str, time: &str| -> Result<DateTime<Local>> {
+        let date = NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+            .or_else(|_| NaiveDate::parse_from_str(date.trim(), "%m/%d/%Y"))
+            .with_context(|| format!("invalid Toggl date '{date}'"))?;
+        let time = NaiveTime::parse_from_str(time.trim(), "%H:%M:%S")
+            .with_context(|| format!("invalid Toggl time '{time}'"))?;
+        local(date.and_time(time))
+    };
+
+    let mut entries = vec![];
+    for record in csv.records() {
+        let r = record?;
+        let get = |i: usize| r.get(i).unwrap_or_default();
+        let mut tag_names: Vec<&str> = tags
+            .map(|i| get(i).split(',').collect())
+            .unwrap_or_default();
+        if let Some(p) = project.map(get).filter(|p| !p.trim().is_empty()) {
+            tag_names.push(p);
+        }
+        let name = get(desc).trim();
+
+        entries.push(Entry {
+            name: if name.is_empty() {
+                "(no description)".to_string()
+            } else {
+                name.to_string()
+            },
+            tags: tag_set(tag_names),
+            start: parse_ts(get(start_date), get(start_time))?,
+            end: Some(parse_ts(get(end_date), get(end_time))?),
+        });
+    }
+    Ok(entries)
+}
+
+/// `i 2024/01/01 10:00:00 Client:Project  description` / `o 2024/01/01 11:00:00`
+pub fn parse_timeclock(text: &str) -> Result<Vec<Entry>> {
+    let parse_ts = |s: &str| -> Result<DateTime<Local>> {
+        let naive = NaiveDateTime::parse_from_str(s, "%Y/%m/%d %H:%M:%S")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S"))
+            .with_context(|| format!("invalid timeclock timestamp '{s}'"))?;
+        local(naive)
+    };
+
+    let mut entries = vec![];
+    let mut open: Option<Entry> = None;
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim_end();
+        let mut fields = line.splitn(4, ' ');
+        let (Some(kind), Some(date), Some(time)) =
+            (fields.next(), fields.next(), fields.next())
+        else {
+            continue;
+        };
+        let ts = || parse_ts(&format!("{date} {time}"));
+        let rest = fields.next().unwrap_or_default().trim();
+
+        match kind {
+            "i" | "I" => {
+                if let Some(prev) = open.take() {
+                    anyhow::bail!(
+                        "line {}: '{}' was never clocked out",
+                        n + 1,
+                        prev.name
+                    );
+                }
+                let (account, payee) = match rest.split_once("  ") {
+                    Some((a, p)) => (a.trim(), p.trim()),
+                    None => (rest, ""),
+                };
+                let name = if payee.is_empty() { account } else { payee };
+                open = Some(Entry {
+                    name: name.to_string(),
+                    tags: tag_set((!account.is_empty()).then_some(account)),
+                    start: ts()?,
+                    end: None,
+                });
+            }
+            "o" | "O" => {
+                let mut entry = open.take().with_context(|| {
+                    format!("line {}: clock-out without clock-in", n + 1)
+                })?;
+                entry.end = Some(ts()?);
+                entries.push(entry);
+            }
+            _ => {}
+        }
+    }
+    entries.extend(open);
+    Ok(entries)
+}
+
+fn describe(e: &Entry) -> String {
+    format!("{} '{}'", e.start.format("%Y-%m-%d %H:%M"), e.name)
+}
+
+/// Merges `entries` into `data`. Entries matching an existing log are reported
+/// as duplicates and entries overlapping one are reported as conflicts; both
+/// are skipped unless `allow_overlap` is set for the latter.
+pub fn merge(data: &mut SaveData, entries: Vec<Entry>, allow_overlap: bool) -> Report {
+    let mut report = Report::default();
+    let now = Local::now();
+
+    for e in entries {
+        let end = e.end.unwrap_or(now);
+        if end < e.start {
+            report
+                .conflicts
+                .push(format!("{}: ends before it starts", describe(&e)));
+            continue;
+        }
+
+        let existing = data.logs.iter().chain(data.past_logs.iter());
+        let mut overlapping = None;
+        let mut duplicate = false;
+        for l in existing {
+            if l.start.timestamp() == e.start.timestamp()
+                && (e.end.is_none() || l.end.timestamp() == end.timestamp())
+                && l.name == e.name
+            {
+                duplicate = true;
+                break;
+            }
+            if overlapping.is_none() && l.start < end && e.start < l.end {
+                overlapping = Some(l.name.clone());
+            }
+        }
+
+        if duplicate {
+            report.duplicates.push(describe(&e));
+            continue;
+        }
+        if let Some(other) = overlapping.filter(|_| !allow_overlap) {
+            report
+                .conflicts
+                .push(format!("{}: overlaps '{other}'", describe(&e)));
+            continue;
+        }
+
+        for t in &e.tags {
+            data.tags.add(t).refs += 1;
+        }
+        let log = Log {
+            id: new_id(),
+            done: e.end.is_some(),
+            name: e.name,
+            start: e.start,
+            end,
+            tags: e.tags,
+            segments: vec![Segment {
+                start: e.start,
+                end,
+            }],
+            paused: false,
+            notes: String::new(),
+        };
+        if log.done {
+            data.past_logs.push(log);
+        } else {
+            data.logs.push(log);
+        }
+        report.added += 1;
+    }
+
+    data.past_logs.sort_by_key(|l| l.start);
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 1, day, hour, min, 0).unwrap()
+    }
+
+    fn entry(name: &str, start: DateTime<Local>, end: Option<DateTime<Local>>) -> Entry {
+        Entry {
+            name: name.to_string(),
+            tags: HashSet::from(["work".to_string()]),
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn parses_timewarrior() {
+        let text = "\
+inc 20250106T090000Z - 20250106T103000Z # client \"deep work\" # \"fix \\\"bug\\\"\"
+inc 20250106T110000Z - 20250106T120000Z # review
+not an interval
+inc 20250106T130000Z # writing
+";
+        let entries = parse_timewarrior(text).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].name, "fix \"bug\"");
+        assert_eq!(entries[0].tags, tag_set(["client", "deep work"]));
+        assert!(entries[0].tags.contains("deep_work"));
+        let utc = |h| {
+            let t = Utc.with_ymd_and_hms(2025, 1, 6, h, 0, 0).unwrap();
+            t.with_timezone(&Local)
+        };
+        assert_eq!(entries[0].start, utc(9));
+        assert_eq!(entries[1].name, "review");
+        assert_eq!(entries[1].end, Some(utc(12)));
+        assert_eq!(entries[2].end, None);
+    }
+
+    #[test]
+    fn timewarrior_untagged_entries_get_a_placeholder() {
+        let entries =
+            parse_timewarrior("inc 20250106T090000Z - 20250106T100000Z\n").unwrap();
+        assert_eq!(entries[0].name, "(untagged)");
+        assert!(entries[0].tags.is_empty());
+        assert!(parse_timewarrior("inc 2025-01-06 # x\n").is_err());
+    }
+
+    #[test]
+    fn parses_toggl() {
+        let csv = "\
+User,Email,Project,Description,Tags,Start date,Start time,End date,End time
+me,me@x,Client A,Write report,\"docs, urgent\",2025-01-06,09:00:00,2025-01-06,10:15:00
+me,me@x,,,,01/07/2025,23:00:00,01/08/2025,01:00:00
+";
+        let entries = parse_toggl(csv.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "Write report");
+        assert_eq!(entries[0].tags, tag_set(["docs", "urgent", "Client A"]));
+        assert_eq!(entries[0].start, at(6, 9, 0));
+        assert_eq!(entries[0].end, Some(at(6, 10, 15)));
+        assert_eq!(entries[1].name, "(no description)");
+        assert!(entries[1].tags.is_empty());
+        assert_eq!(entries[1].end, Some(at(8, 1, 0)));
+    }
+
+    #[test]
+    fn toggl_needs_the_time_columns() {
+        let csv = "Description,Start date\nx,2025-01-06\n";
+        assert!(parse_toggl(csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn parses_timeclock() {
+        let text = "\
+i 2025/01/06 09:00:00 Client:Project  Planning
+o 2025/01/06 10:00:00
+; a comment
+i 2025-01-06 11:00:00 Admin
+";
+        let entries = parse_timeclock(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "Planning");
+        assert_eq!(entries[0].tags, tag_set(["Client:Project"]));
+        assert_eq!(entries[0].end, Some(at(6, 10, 0)));
+        assert_eq!(entries[1].name, "Admin");
+        assert_eq!(entries[1].start, at(6, 11, 0));
+        assert_eq!(entries[1].end, None);
+    }
+
+    #[test]
+    fn timeclock_needs_matching_clock_ins() {
+        assert!(parse_timeclock("o 2025/01/06 10:00:00\n").is_err());
+        let twice = "i 2025/01/06 09:00:00 A\ni 2025/01/06 10:00:00 B\n";
+        assert!(parse_timeclock(twice).is_err());
+    }
+
+    #[test]
+    fn merge_adds_entries_to_the_right_list() {
+        let mut data = SaveData::new();
+        let entries = vec![
+            entry("done", at(6, 9, 0), Some(at(6, 10, 0))),
+            entry("running", at(6, 11, 0), None),
+        ];
+        let report = merge(&mut data, entries, false);
+        assert_eq!(report.added, 2);
+        assert_eq!(data.past_logs[0].name, "done");
+        assert!(data.past_logs[0].done);
+        assert_eq!(data.logs[0].name, "running");
+        assert_eq!(data.tags.tags()[0].refs, 2);
+    }
+
+    #[test]
+    fn merge_skips_duplicates() {
+        let mut data = SaveData::new();
+        let e = entry("a", at(6, 9, 0), Some(at(6, 10, 0)));
+        merge(&mut data, vec![e.clone()], false);
+        let report = merge(&mut data, vec![e], false);
+        assert_eq!(report.added, 0);
+        assert_eq!(report.duplicates.len(), 1);
+        assert_eq!(data.past_logs.len(), 1);
+    }
+
+    #[test]
+    fn merge_reports_conflicts() {
+        let mut data = SaveData::new();
+        merge(
+            &mut data,
+            vec![entry("a", at(6, 9, 0), Some(at(6, 10, 0)))],
+            false,
+        );
+
+        let overlapping = entry("b", at(6, 9, 30), Some(at(6, 10, 30)));
+        let backwards = entry("c", at(6, 12, 0), Some(at(6, 11, 0)));
+        let report = merge(&mut data, vec![overlapping.clone(), backwards], false);
+        assert_eq!(report.added, 0);
+        assert_eq!(report.conflicts.len(), 2);
+        assert!(report.conflicts[0].contains("overlaps 'a'"));
+
+        let report = merge(&mut data, vec![overlapping], true);
+        assert_eq!(report.added, 1);
+        assert_eq!(data.past_logs.len(), 2);
+    }
+}
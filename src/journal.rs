@@ -0,0 +1,928 @@
+use crate::{
+    data::SaveData,
+    log::Log,
+    migrate,
+    tag::{Tag, TagSys},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    mem,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// Events after the last snapshot before the data file is rewritten.
+pub const COMPACT_EVERY: u64 = 200;
+
+/// One line of the journal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Event {
+    pub at: DateTime<Local>,
+    #[serde(flatten)]
+    pub change: Change,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Change {
+    /// The whole state, written when a journal is started.
+    Snapshot {
+        /// Number of events recorded before it, in rotated journals.
+        #[serde(default)]
+        base: u64,
+        logs: Vec<Log>,
+        past_logs: Vec<Log>,
+        tags: TagSys,
+    },
+    /// Replaces `removed` at `index` of the active or past logs with
+    /// `inserted`.
+    Splice {
+        past: bool,
+        index: usize,
+        removed: Vec<Log>,
+        inserted: Vec<Log>,
+    },
+    /// Tags were added, removed or recolored. Refs aren't kept up to date,
+    /// as they follow from the logs.
+    Tags { tags: TagSys },
+}
+
+fn names(logs: &[Log]) -> String {
+    let names: Vec<String> = logs.iter().map(|l| format!("'{}'", l.name)).collect();
+    names.join(", ")
+}
+
+impl Change {
+    pub fn describe(&self) -> String {
+        match self {
+            Change::Snapshot {
+                logs, past_logs, ..
+            } => format!(
+                "snapshot of {} active and {} past logs",
+                logs.len(),
+                past_logs.len()
+            ),
+            Change::Splice {
+                past,
+                removed,
+                inserted,
+                ..
+            } => {
+                let list = if *past { "past" } else { "active" };
+                match (removed.is_empty(), inserted.is_empty()) {
+                    (true, _) => format!("added {} to {list} logs", names(inserted)),
+                    (_, true) => format!("removed {} from {list} logs", names(removed)),
+                    _ => format!("edited {} in {list} logs", names(inserted)),
+                }
+            }
+            Change::Tags { tags } => {
+                format!("updated tags ({} total)", tags.tags().len())
+            }
+        }
+    }
+}
+
+/// The parts of [`SaveData`] the journal tracks.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Tracked {
+    pub logs: Vec<Log>,
+    pub past_logs: Vec<Log>,
+    pub tags: TagSys,
+}
+
+impl Change {
+    /// Applies the change to the given parts of [`SaveData`].
+    pub fn apply(
+        &self,
+        logs: &mut Vec<Log>,
+        past_logs: &mut Vec<Log>,
+        tags: &mut TagSys,
+    ) -> Result<()> {
+        match self {
+            Change::Snapshot {
+                logs: l,
+                past_logs: p,
+                tags: t,
+                ..
+            } => {
+                *logs = l.clone();
+                *past_logs = p.clone();
+                *tags = t.clone();
+            }
+            Change::Splice {
+                past,
+                index,
+                removed,
+                inserted,
+            } => {
+                let logs = if *past { past_logs } else { logs };
+                anyhow::ensure!(
+                    index + removed.len() <= logs.len(),
+                    "splice past the end of the logs"
+                );
+                logs.splice(*index..index + removed.len(), inserted.iter().cloned());
+            }
+            Change::Tags { tags: t } => *tags = t.clone(),
+        }
+        Ok(())
+    }
+}
+
+/// Changes that turn the logs and tags of `old` into those of `new`.
+pub fn diff(
+    (old_logs, old_past, old_tags): (&[Log], &[Log], &TagSys),
+    (logs, past_logs, tags): (&[Log], &[Log], &TagSys),
+) -> Vec<Change> {
+    let mut changes = vec![];
+    for (past, old, new) in [(false, old_logs, logs), (true, old_past, past_logs)] {
+        let same = |(a, b): &(&Log, &Log)| Tracked::same(a, b, !past);
+        let prefix = old.iter().zip(new).take_while(same).count();
+        if prefix == old.len() && prefix == new.len() {
+            continue;
+        }
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(same)
+            .count();
+        changes.push(Change::Splice {
+            past,
+            index: prefix,
+            removed: old[prefix..old.len() - suffix].to_vec(),
+            inserted: new[prefix..new.len() - suffix].to_vec(),
+        });
+    }
+    let same_tags = old_tags.tags().len() == tags.tags().len()
+        && old_tags
+            .tags()
+            .iter()
+            .zip(tags.tags())
+            .all(|(a, b)| a.name() == b.name() && a.color() == b.color());
+    if !same_tags {
+        changes.push(Change::Tags { tags: tags.clone() });
+    }
+    changes
+}
+
+impl Tracked {
+    fn apply(&mut self, change: &Change) -> Result<()> {
+        change.apply(&mut self.logs, &mut self.past_logs, &mut self.tags)
+    }
+
+    /// Running logs only change by ticking, which isn't journaled.
+    fn same(a: &Log, b: &Log, active: bool) -> bool {
+        if !active || a.paused || b.paused {
+            return a == b;
+        }
+        let settle = |l: &Log| {
+            let mut l = l.clone();
+            l.end = l.start;
+            if let Some(seg) = l.segments.last_mut() {
+                seg.end = seg.start;
+            }
+            l
+        };
+        settle(a) == settle(b)
+    }
+
+    fn parts(&self) -> (&[Log], &[Log], &TagSys) {
+        (&self.logs, &self.past_logs, &self.tags)
+    }
+
+    fn rebuild_refs(&mut self) {
+        let Tracked {
+            logs,
+            past_logs,
+            tags,
+        } = self;
+        tags.rebuild_refs(logs.iter().chain(past_logs.iter()));
+    }
+}
+
+/// Append-only log of the changes to the data since the last compaction,
+/// stored next to the data file and opened by a snapshot. Events are
+/// numbered from the first one ever recorded; the data file includes the
+/// first `journal_seq` of them. Older events are archived next to it by
+/// [`Journal::rotate`].
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    /// Number of the first event in the file.
+    base: u64,
+    /// Number of events in the file.
+    len: u64,
+    /// State after the last event, which the next save is diffed against.
+    last: Tracked,
+}
+
+impl Journal {
+    pub fn path_for(data_path: &Path) -> PathBuf {
+        PathBuf::from(format!("{}.journal", data_path.display()))
+    }
+
+    /// Where the events from `base` on are kept once the journal at `path`
+    /// is rotated.
+    fn archive_path(path: &Path, base: u64) -> PathBuf {
+        PathBuf::from(format!("{}.{base}", path.display()))
+    }
+
+    /// Opens the journal at `path` and replays the events `data` doesn't
+    /// include yet. A missing journal is started with a snapshot of `data`,
+    /// and a torn last line, left by a crash mid-write, is cut off.
+    pub fn open(path: PathBuf, data: &mut SaveData) -> Result<Journal> {
+        let (events, torn) = if fs::exists(&path)? {
+            read_file(&path)?
+        } else {
+            (vec![], None)
+        };
+        if let Some(valid) = torn {
+            warn!("cutting off torn last line of {}", path.display());
+            OpenOptions::new().write(true).open(&path)?.set_len(valid)?;
+        }
+        let base = base(&events);
+        let end = base + events.len() as u64;
+        if end < data.journal_seq || events.is_empty() {
+            if !events.is_empty() {
+                let aside =
+                    format!("{}.orphan-{}", path.display(), Local::now().timestamp());
+                warn!("journal is behind the data file, moving it to {aside}");
+                fs::rename(&path, &aside)?;
+            }
+            let mut journal = Journal {
+                path,
+                base: data.journal_seq,
+                len: 0,
+                last: Tracked::default(),
+            };
+            journal.append(vec![journal.snapshot(data)])?;
+            data.journal_seq = journal.end();
+            return Ok(journal);
+        }
+
+        Ok(Journal {
+            path,
+            base,
+            len: events.len() as u64,
+            last: catch_up(&events, base, data)?,
+        })
+    }
+
+    fn snapshot(&self, data: &SaveData) -> Change {
+        Change::Snapshot {
+            base: self.end(),
+            logs: data.logs.clone(),
+            past_logs: data.past_logs.clone(),
+            tags: data.tags.clone(),
+        }
+    }
+
+    /// Number of events in the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Number of events ever recorded, including rotated ones.
+    pub fn end(&self) -> u64 {
+        self.base + self.len
+    }
+
+    /// State after the last event.
+    pub fn last(&self) -> &Tracked {
+        &self.last
+    }
+
+    /// Appends whatever changed since the last call.
+    pub fn record(
+        &mut self,
+        logs: &[Log],
+        past_logs: &[Log],
+        tags: &TagSys,
+    ) -> Result<()> {
+        let changes = diff(self.last.parts(), (logs, past_logs, tags));
+        if changes.is_empty() {
+            return Ok(());
+        }
+        self.append(changes)
+    }
+
+    /// Archives the events so far and starts over with a snapshot of
+    /// `data`, which must already be saved with all of them, so opening and
+    /// merging only read what happened since.
+    pub fn rotate(&mut self, data: &SaveData) -> Result<()> {
+        let snapshot = self.snapshot(data);
+        let archive = Journal::archive_path(&self.path, self.base);
+        fs::rename(&self.path, &archive)?;
+        let old = (self.base, self.len, mem::take(&mut self.last));
+        self.base = self.end();
+        self.len = 0;
+        if let Err(e) = self.append(vec![snapshot]) {
+            let _ = fs::remove_file(&self.path);
+            fs::rename(&archive, &self.path)?;
+            (self.base, self.len, self.last) = old;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn append(&mut self, changes: Vec<Change>) -> Result<()> {
+        let at = Local::now();
+        let mut lines = String::new();
+        for change in &changes {
+            let event = Event {
+                at,
+                change: change.clone(),
+            };
+            lines.push_str(&serde_json::to_string(&event)?);
+            lines.push('\n');
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_data()?;
+
+        for change in &changes {
+            self.last.apply(change)?;
+        }
+        self.last.rebuild_refs();
+        self.len += changes.len() as u64;
+        Ok(())
+    }
+}
+
+/// Number of the first of `events`, read from a journal file.
+fn base(events: &[Event]) -> u64 {
+    match events.first() {
+        Some(Event {
+            change: Change::Snapshot { base, .. },
+            ..
+        }) => *base,
+        _ => 0,
+    }
+}
+
+/// Replays the `events` numbered from `base` that `data` doesn't include
+/// yet and returns the resulting state.
+fn catch_up(events: &[Event], base: u64, data: &mut SaveData) -> Result<Tracked> {
+    let mut tracked = Tracked {
+        logs: mem::take(&mut data.logs),
+        past_logs: mem::take(&mut data.past_logs),
+        tags: mem::take(&mut data.tags),
+    };
+    // a data file older than the snapshot starts over from it
+    let skip = data.journal_seq.saturating_sub(base) as usize;
+    let pending = events.get(skip..).unwrap_or_default();
+    for (i, e) in pending.iter().enumerate() {
+        tracked.apply(&e.change).with_context(|| {
+            format!("replaying journal event {}", base as usize + skip + i)
+        })?;
+    }
+    if !pending.is_empty() {
+        info!("replayed {} journal events", pending.len());
+    }
+    tracked.rebuild_refs();
+    data.logs = tracked.logs.clone();
+    data.past_logs = tracked.past_logs.clone();
+    data.tags = tracked.tags.clone();
+    Ok(tracked)
+}
+
+/// Brings `data` up to date with the journal at `path` like
+/// [`Journal::open`] does, but without writing to it. A torn last line is
+/// skipped.
+pub fn replay(path: &Path, data: &mut SaveData) -> Result<()> {
+    if !fs::exists(path)? {
+        return Ok(());
+    }
+    let (events, _) = read_file(path)?;
+    catch_up(&events, base(&events), data)?;
+    Ok(())
+}
+
+/// Events of the journal file at `path`, and the length of its valid part
+/// if the last line is torn.
+fn read_file(path: &Path) -> Result<(Vec<Event>, Option<u64>)> {
+    let file = File::open(path)
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut events = vec![];
+    let (mut line, mut offset) = (String::new(), 0);
+    let mut bad = None;
+    for n in 1.. {
+        line.clear();
+        let read = reader.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+        if let Some((n, e)) = bad.take() {
+            // only the last line may be torn
+            return Err(e).with_context(|| format!("invalid journal line {n}"));
+        }
+        if !line.trim().is_empty() {
+            match serde_json::from_str(&line)
+                .map_err(anyhow::Error::from)
+                .and_then(parse_event)
+            {
+                Ok(event) => events.push(event),
+                Err(e) => bad = Some((n, e)),
+            }
+        }
+        if bad.is_none() {
+            offset += read as u64;
+        }
+    }
+    Ok((events, bad.map(|_| offset)))
+}
+
+/// Every event recorded in the journal at `path`, including archived ones,
+/// oldest first. A torn last line is skipped.
+pub fn read(path: &Path) -> Result<Vec<Event>> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        anyhow::bail!("invalid journal path {}", path.display());
+    };
+    let prefix = format!("{}.", name.to_string_lossy());
+    let mut archives: Vec<u64> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            name.strip_prefix(&prefix)?.parse().ok()
+        })
+        .collect();
+    archives.sort();
+
+    let mut events = vec![];
+    let files = archives
+        .into_iter()
+        .map(|base| Journal::archive_path(path, base));
+    for file in files.chain([path.to_path_buf()]) {
+        let (mut read, torn) = read_file(&file)?;
+        if torn.is_some() {
+            warn!("skipping torn last line of {}", file.display());
+        }
+        events.append(&mut read);
+    }
+    Ok(events)
+}
+
+/// Parses an event, giving logs written before logs had ids the ids
+/// [`migrate`] gives them in the data file.
+fn parse_event(mut raw: Value) -> Result<Event> {
+    if let Some(event) = raw.as_object_mut() {
+        let mut list = |key| event.get_mut(key).map(mem::take).unwrap_or_default();
+        let (mut logs, mut past_logs) = (list("logs"), list("past_logs"));
+        let (mut removed, mut inserted) = (list("removed"), list("inserted"));
+        migrate::add_log_ids([&mut logs, &mut past_logs])?;
+        // the same log on both sides of an edit must keep its id
+        migrate::add_log_ids([&mut removed])?;
+        migrate::add_log_ids([&mut inserted])?;
+        for (key, value) in [
+            ("logs", logs),
+            ("past_logs", past_logs),
+            ("removed", removed),
+            ("inserted", inserted),
+        ] {
+            if !value.is_null() {
+                event.insert(key.to_string(), value);
+            }
+        }
+    }
+    Ok(serde_json::from_value(raw)?)
+}
+
+/// What `ours` did to the tags of `base` it no longer has, judging by the
+/// logs both still have: moved to another tag by a rename or merge
+/// (`Some`), or stripped from the logs (`None`). Tags that only went away
+/// along with their logs aren't included.
+fn tag_moves(base: &Tracked, ours: &Tracked) -> HashMap<String, Option<String>> {
+    let names: HashSet<&str> = ours.tags.tags().iter().map(|t| t.name()).collect();
+    let our_logs: HashMap<&str, &Log> = ours
+        .logs
+        .iter()
+        .chain(&ours.past_logs)
+        .map(|l| (l.id.as_str(), l))
+        .collect();
+
+    // tags each gone tag could have become, on every log that had it
+    let mut candidates: HashMap<&str, HashSet<&str>> = HashMap::new();
+    for before in base.logs.iter().chain(&base.past_logs) {
+        let Some(after) = our_logs.get(before.id.as_str()) else {
+            continue;
+        };
+        let added: HashSet<&str> = after
+            .tags
+            .difference(&before.tags)
+            .map(|t| t.as_str())
+            .collect();
+        for tag in before.tags.iter().filter(|t| !names.contains(t.as_str())) {
+            candidates
+                .entry(tag)
+                .and_modify(|c| c.retain(|t| added.contains(t)))
+                .or_insert_with(|| added.clone());
+        }
+    }
+
+    // a renamed parent moves its children along, keeping their own names,
+    // so a target with the same name belongs to that tag and no other
+    let last = |t: &str| t.rsplit(':').next().unwrap_or_default().to_string();
+    let mut moves = HashMap::new();
+    for (tag, targets) in &candidates {
+        let same: Vec<&&str> = targets.iter().filter(|t| last(t) == last(tag)).collect();
+        if let [to] = same[..] {
+            moves.insert(tag.to_string(), Some(to.to_string()));
+        }
+    }
+    let claimed: HashSet<String> = moves.values().flatten().cloned().collect();
+    for (tag, mut targets) in candidates {
+        if moves.contains_key(tag) {
+            continue;
+        }
+        targets.retain(|t| !claimed.contains(*t));
+        let to = match targets.len() {
+            0 => None,
+            1 => targets.into_iter().next().map(str::to_string),
+            _ => continue,
+        };
+        moves.insert(tag.to_string(), to);
+    }
+    moves
+}
+
+/// What one copy of the data changed since an earlier state, by log id, so
+/// it can be applied to another copy that changed in the meantime.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Patch {
+    /// Ids of deleted logs.
+    removed: Vec<String>,
+    /// Added or changed logs, and whether they are past logs.
+    changed: Vec<(bool, Log)>,
+    /// See [`tag_moves`].
+    tag_moves: HashMap<String, Option<String>>,
+    /// Added or recolored tags.
+    tags: Vec<Tag>,
+}
+
+impl Patch {
+    /// The changes that turned `base` into `ours`.
+    pub fn between(base: &Tracked, ours: &Tracked) -> Patch {
+        let index = |t: &Tracked| {
+            let mut map = HashMap::new();
+            for (past, logs) in [(false, &t.logs), (true, &t.past_logs)] {
+                for log in logs {
+                    map.insert(log.id.clone(), (past, log.clone()));
+                }
+            }
+            map
+        };
+        let base_logs = index(base);
+        let our_logs = index(ours);
+
+        let mut patch = Patch {
+            removed: base_logs
+                .keys()
+                .filter(|id| !our_logs.contains_key(*id))
+                .cloned()
+                .collect(),
+            tag_moves: tag_moves(base, ours),
+            ..Default::default()
+        };
+        for (past, logs) in [(false, &ours.logs), (true, &ours.past_logs)] {
+            for log in logs {
+                let changed = match base_logs.get(&log.id) {
+                    Some((was_past, before)) => {
+                        *was_past != past || !Tracked::same(before, log, !past)
+                    }
+                    None => true,
+                };
+                if changed {
+                    patch.changed.push((past, log.clone()));
+                }
+            }
+        }
+        for tag in ours.tags.tags() {
+            let unchanged = base
+                .tags
+                .tags()
+                .iter()
+                .any(|t| t.name() == tag.name() && t.color() == tag.color());
+            if !unchanged {
+                patch.tags.push(tag.clone());
+            }
+        }
+        patch
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Patch::default()
+    }
+
+    /// Makes the same changes to `theirs`. A log changed on both sides ends
+    /// up as in the patch.
+    pub fn apply(&self, theirs: &mut Tracked) {
+        for id in &self.removed {
+            theirs.logs.retain(|l| l.id != *id);
+            theirs.past_logs.retain(|l| l.id != *id);
+        }
+        for (past, log) in &self.changed {
+            let (list, other) = if *past {
+                (&mut theirs.past_logs, &mut theirs.logs)
+            } else {
+                (&mut theirs.logs, &mut theirs.past_logs)
+            };
+            other.retain(|l| l.id != log.id);
+            match list.iter_mut().find(|l| l.id == log.id) {
+                Some(l) => *l = log.clone(),
+                None => list.push(log.clone()),
+            }
+        }
+
+        for log in theirs.logs.iter_mut().chain(theirs.past_logs.iter_mut()) {
+            for (from, to) in &self.tag_moves {
+                if log.tags.remove(from)
+                    && let Some(to) = to
+                {
+                    log.tags.insert(to.clone());
+                }
+            }
+        }
+        for tag in &self.tags {
+            theirs.tags.add(tag.name()).set_color(*tag.color());
+        }
+        theirs.rebuild_refs();
+    }
+}
+
+/// Three-way merge that applies the changes `ours` made since `base` on top
+/// of `theirs`. Logs are matched by id, so a log changed on both sides ends
+/// up as ours. Tags `ours` renamed, merged or stripped are also renamed,
+/// merged or stripped on the logs `theirs` added.
+pub fn merge(base: &Tracked, ours: &Tracked, theirs: &Tracked) -> Tracked {
+    let mut merged = theirs.clone();
+    Patch::between(base, ours).apply(&mut merged);
+    merged
+}
+
+/// Replays `events` up to `at`, with running logs ticked to that moment.
+pub fn state_at(events: &[Event], at: DateTime<Local>) -> Result<Tracked> {
+    let mut tracked = Tracked::default();
+    for e in events.iter().take_while(|e| e.at <= at) {
+        tracked.apply(&e.change)?;
+    }
+    tracked.rebuild_refs();
+    for log in tracked.logs.iter_mut().filter(|l| !l.paused) {
+        log.tick(at);
+    }
+    Ok(tracked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::Segment;
+    use chrono::TimeZone;
+
+    fn log(name: &str, tags: &[&str]) -> Log {
+        let start = Local.with_ymd_and_hms(2026, 1, 1, 9, 0, 0).unwrap();
+        let tags = tags.iter().map(|t| t.to_string()).collect();
+        let mut log = Log::new(name.to_string(), tags);
+        log.start = start;
+        log.end = start;
+        log.segments = vec![Segment { start, end: start }];
+        log.done = true;
+        log
+    }
+
+    fn tracked(logs: Vec<Log>, past_logs: Vec<Log>) -> Tracked {
+        let mut tags = TagSys::default();
+        tags.rebuild_refs(logs.iter().chain(&past_logs));
+        Tracked {
+            logs,
+            past_logs,
+            tags,
+        }
+    }
+
+    fn names(logs: &[Log]) -> Vec<&str> {
+        logs.iter().map(|l| l.name.as_str()).collect()
+    }
+
+    #[test]
+    fn merge_keeps_logs_that_start_together() {
+        let (a, b) = (log("a", &[]), log("b", &[]));
+        let base = tracked(vec![a.clone()], vec![]);
+        let ours = tracked(vec![a.clone(), b], vec![]);
+        let theirs = tracked(vec![a, log("c", &[])], vec![]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(names(&merged.logs), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn merge_applies_concurrent_adds_deletes_and_edits() {
+        let (a, b, c) = (log("a", &[]), log("b", &[]), log("c", &[]));
+        let base = tracked(vec![a.clone(), b.clone(), c.clone()], vec![]);
+
+        let mut edited = a.clone();
+        edited.name = "a2".to_string();
+        let ours = tracked(vec![edited, c.clone()], vec![b.clone()]);
+        let theirs = tracked(vec![a, b, log("d", &[])], vec![]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(names(&merged.logs), ["a2", "d"]);
+        assert_eq!(names(&merged.past_logs), ["b"]);
+    }
+
+    #[test]
+    fn merge_carries_tag_renames_and_removals() {
+        let a = log("a", &["work:x", "old"]);
+        let base = tracked(vec![a.clone()], vec![]);
+
+        let mut renamed = a.clone();
+        renamed.tags = ["job:x".to_string()].into();
+        let ours = tracked(vec![renamed], vec![]);
+        let theirs = tracked(vec![a, log("b", &["work:x", "old", "new"])], vec![]);
+
+        let merged = merge(&base, &ours, &theirs);
+        let tags: HashSet<&str> =
+            merged.logs[1].tags.iter().map(|t| t.as_str()).collect();
+        assert_eq!(tags, HashSet::from(["job:x", "new"]));
+        let names: Vec<&str> = merged.tags.tags().iter().map(|t| t.name()).collect();
+        assert!(!names.contains(&"work:x") && !names.contains(&"old"));
+        assert!(names.contains(&"job:x"));
+    }
+
+    #[test]
+    fn merge_keeps_tags_that_went_with_their_logs() {
+        let a = log("a", &["gone"]);
+        let base = tracked(vec![a.clone()], vec![]);
+        let ours = tracked(vec![], vec![]);
+        let theirs = tracked(vec![a, log("b", &["gone"])], vec![]);
+
+        let merged = merge(&base, &ours, &theirs);
+        assert_eq!(names(&merged.logs), ["b"]);
+        assert!(merged.logs[0].tags.contains("gone"));
+        assert_eq!(merged.tags.tags()[0].refs, 1);
+    }
+
+    fn at(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 1, 1, hour, 0, 0).unwrap()
+    }
+
+    fn event(hour: u32, change: Change) -> Event {
+        Event {
+            at: at(hour),
+            change,
+        }
+    }
+
+    #[test]
+    fn open_replays_events_the_data_lacks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save.dat.journal");
+        let mut data = SaveData::new();
+        let mut journal = Journal::open(path.clone(), &mut data).unwrap();
+        assert_eq!(data.journal_seq, 1);
+
+        data.logs.push(log("a", &["t"]));
+        data.tags.rebuild_refs(data.logs.iter());
+        journal
+            .record(&data.logs, &data.past_logs, &data.tags)
+            .unwrap();
+        data.logs[0].name = "b".to_string();
+        journal
+            .record(&data.logs, &data.past_logs, &data.tags)
+            .unwrap();
+
+        let mut stale = SaveData::new();
+        stale.journal_seq = 1;
+        let reopened = Journal::open(path, &mut stale).unwrap();
+        assert_eq!(names(&stale.logs), ["b"]);
+        assert_eq!(stale.tags, data.tags);
+        assert_eq!(reopened.end(), journal.end());
+        assert_eq!(reopened.last(), journal.last());
+    }
+
+    #[test]
+    fn ref_counts_alone_are_not_recorded() {
+        let a = log("a", &["t"]);
+        let old = tracked(vec![a.clone()], vec![]);
+        let new = tracked(vec![a.clone(), log("b", &["t"])], vec![]);
+        assert_eq!(new.tags.tags()[0].refs, 2);
+
+        let changes = diff(old.parts(), new.parts());
+        assert!(matches!(changes[..], [Change::Splice { .. }]));
+    }
+
+    #[test]
+    fn state_at_replays_up_to_the_moment() {
+        let a = log("a", &[]);
+        let mut edited = a.clone();
+        edited.name = "a2".to_string();
+        let events = [
+            event(
+                9,
+                Change::Snapshot {
+                    base: 0,
+                    logs: vec![a.clone()],
+                    past_logs: vec![],
+                    tags: TagSys::default(),
+                },
+            ),
+            event(
+                10,
+                Change::Splice {
+                    past: false,
+                    index: 0,
+                    removed: vec![a],
+                    inserted: vec![edited.clone()],
+                },
+            ),
+            event(
+                11,
+                Change::Splice {
+                    past: false,
+                    index: 0,
+                    removed: vec![edited.clone()],
+                    inserted: vec![],
+                },
+            ),
+        ];
+
+        assert_eq!(names(&state_at(&events, at(9)).unwrap().logs), ["a"]);
+        assert_eq!(names(&state_at(&events, at(10)).unwrap().logs), ["a2"]);
+        assert!(state_at(&events, at(12)).unwrap().logs.is_empty());
+        assert!(state_at(&events, at(8)).unwrap().logs.is_empty());
+    }
+
+    #[test]
+    fn state_at_ticks_running_logs() {
+        let mut running = log("a", &[]);
+        running.done = false;
+        let events = [event(
+            9,
+            Change::Snapshot {
+                base: 0,
+                logs: vec![running],
+                past_logs: vec![],
+                tags: TagSys::default(),
+            },
+        )];
+        let state = state_at(&events, at(11)).unwrap();
+        assert_eq!(state.logs[0].duration().num_hours(), 2);
+    }
+
+    #[test]
+    fn torn_last_line_is_cut_off() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save.dat.journal");
+        let mut data = SaveData::new();
+        Journal::open(path.clone(), &mut data).unwrap();
+        let len = fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"at":"2026-01-01T09:"#).unwrap();
+
+        let mut reopened = SaveData::new();
+        reopened.journal_seq = 1;
+        let mut journal = Journal::open(path.clone(), &mut reopened).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        reopened.logs.push(log("a", &[]));
+        let SaveData {
+            logs,
+            past_logs,
+            tags,
+            ..
+        } = &reopened;
+        journal.record(logs, past_logs, tags).unwrap();
+        assert_eq!(read(&path).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn corrupt_middle_line_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save.dat.journal");
+        let mut data = SaveData::new();
+        let mut journal = Journal::open(path.clone(), &mut data).unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"garbage\n")
+            .unwrap();
+        data.logs.push(log("a", &[]));
+        journal
+            .record(&data.logs, &data.past_logs, &data.tags)
+            .unwrap();
+
+        let mut reopened = SaveData::new();
+        let err = Journal::open(path, &mut reopened).unwrap_err();
+        assert!(format!("{err:#}").contains("invalid journal line 2"));
+    }
+}
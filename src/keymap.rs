@@ -0,0 +1,439 @@
+use anyhow::{Context, Result};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Everything a key can be bound to outside of the input dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    Help,
+    NewLog,
+    Delete,
+    Complete,
+    Pause,
+    NextItem,
+    PrevItem,
+    FirstItem,
+    LastItem,
+    NextTab,
+    PrevTab,
+    NextRange,
+    PrevRange,
+    Export,
+    CustomRange,
+    Undo,
+    Redo,
+    ToggleFold,
+    MergeTag,
+    StripTag,
+    DeleteTagged,
+    Open,
+    EditNotes,
+    EditLog,
+}
+
+/// Config name and default keys of every action.
+const DEFAULTS: [(Action, &str, &[&str]); 25] = [
+    (Action::Quit, "quit", &["q"]),
+    (Action::Help, "help", &["?"]),
+    (Action::NewLog, "new_log", &["A"]),
+    (Action::Delete, "delete", &["D", "dd"]),
+    (Action::Complete, "complete", &["enter"]),
+    (Action::Pause, "pause", &["p"]),
+    (Action::NextItem, "next_item", &["j", "n", "down"]),
+    (Action::PrevItem, "prev_item", &["k", "m", "up"]),
+    (Action::FirstItem, "first_item", &["gg", "home"]),
+    (Action::LastItem, "last_item", &["G", "end"]),
+    (Action::NextTab, "next_tab", &["J", "tab"]),
+    (Action::PrevTab, "prev_tab", &["K", "backtab"]),
+    (Action::NextRange, "next_range", &["l", "right"]),
+    (Action::PrevRange, "prev_range", &["h", "left"]),
+    (Action::Export, "export", &["E"]),
+    (Action::CustomRange, "custom_range", &["c"]),
+    (Action::Undo, "undo", &["u"]),
+    (Action::Redo, "redo", &["ctrl-r"]),
+    (Action::ToggleFold, "toggle_fold", &["space"]),
+    (Action::MergeTag, "merge_tag", &["M"]),
+    (Action::StripTag, "strip_tag", &[]),
+    (Action::DeleteTagged, "delete_tagged", &[]),
+    (Action::Open, "open", &["o"]),
+    (Action::EditNotes, "edit_notes", &["e"]),
+    (Action::EditLog, "edit_log", &["r"]),
+];
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        DEFAULTS.iter().find(|d| d.0 == self).map_or("", |d| d.1)
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        DEFAULTS.iter().find(|d| d.1 == name).map(|d| d.0)
+    }
+
+    /// Whether the action only moves around or changes what is shown,
+    /// leaving the data as it is.
+    pub fn is_navigation(self) -> bool {
+        matches!(
+            self,
+            Action::NextItem
+                | Action::PrevItem
+                | Action::FirstItem
+                | Action::LastItem
+                | Action::NextRange
+                | Action::PrevRange
+                | Action::ToggleFold
+                | Action::Open
+        )
+    }
+}
+
+/// One key or a list of alternatives, as written in the `[keys]` table.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum KeySpec {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeySpec {
+    fn specs(&self) -> Vec<&str> {
+        match self {
+            KeySpec::One(s) => vec![s.as_str()],
+            KeySpec::Many(v) => v.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+/// Result of feeding a key to the [`Keymap`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolved {
+    Action(Action),
+    /// The keys so far are the start of a longer sequence.
+    Pending,
+    Unbound,
+}
+
+/// Key sequences bound to actions. Keys of a sequence are collected in a
+/// caller-owned buffer until they match a binding or can no longer match.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Vec<KeyEvent>, Action)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&BTreeMap::new()).expect("default keymap is valid")
+    }
+}
+
+impl Keymap {
+    /// Builds the keymap from the defaults, with every action listed in
+    /// `overrides` replacing its default keys.
+    pub fn new(overrides: &BTreeMap<String, KeySpec>) -> Result<Keymap> {
+        for name in overrides.keys() {
+            if Action::from_name(name).is_none() {
+                anyhow::bail!("unknown action '{name}' in [keys]");
+            }
+        }
+
+        let mut bindings = vec![];
+        for (action, name, defaults) in DEFAULTS {
+            let specs = match overrides.get(name) {
+                Some(spec) => spec.specs(),
+                None => defaults.to_vec(),
+            };
+            for spec in specs {
+                let seq = parse_sequence(spec)
+                    .with_context(|| format!("invalid key for '{name}'"))?;
+                bindings.push((seq, action));
+            }
+        }
+
+        // a binding equal to or a prefix of another would make the other
+        // one unreachable
+        for (i, a) in bindings.iter().enumerate() {
+            for b in &bindings[i + 1..] {
+                let ((seq, action), (other, other_action)) = if a.0.len() <= b.0.len() {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                if seq_matches(seq, &other[..seq.len()]) {
+                    anyhow::bail!(
+                        "key '{}' for {} shadows '{}' for {}",
+                        format_sequence(seq),
+                        action.name(),
+                        format_sequence(other),
+                        other_action.name()
+                    );
+                }
+            }
+        }
+        Ok(Keymap { bindings })
+    }
+
+    /// Feeds `key` to the sequence collected in `pending`. The buffer is
+    /// cleared whenever it resolves; a key that breaks a sequence is tried
+    /// again on its own.
+    pub fn resolve(&self, pending: &mut Vec<KeyEvent>, key: KeyEvent) -> Resolved {
+        pending.push(key);
+        let mut prefix = false;
+        for (seq, action) in &self.bindings {
+            if seq.len() == pending.len() && seq_matches(seq, pending) {
+                pending.clear();
+                return Resolved::Action(*action);
+            }
+            prefix |=
+                seq.len() > pending.len() && seq_matches(&seq[..pending.len()], pending);
+        }
+        if prefix {
+            return Resolved::Pending;
+        }
+
+        let retry = pending.len() > 1;
+        pending.clear();
+        if retry {
+            self.resolve(pending, key)
+        } else {
+            Resolved::Unbound
+        }
+    }
+
+    /// Keys bound to `action`, formatted like they are written in the config.
+    pub fn keys_for(&self, action: Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(seq, _)| format_sequence(seq))
+            .collect()
+    }
+}
+
+fn seq_matches(bound: &[KeyEvent], keys: &[KeyEvent]) -> bool {
+    bound.len() == keys.len() && bound.iter().zip(keys).all(|(b, k)| key_matches(b, k))
+}
+
+/// Whether `key` is the key described by `bound`. Shift is implied by the
+/// case of character keys and by backtab, so it is ignored for them.
+pub fn key_matches(bound: &KeyEvent, key: &KeyEvent) -> bool {
+    let ignore = match key.code {
+        KeyCode::Char(_) | KeyCode::BackTab => KeyModifiers::SHIFT,
+        _ => KeyModifiers::NONE,
+    };
+    bound.code == key.code && (bound.modifiers - ignore) == (key.modifiers - ignore)
+}
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    Some(match name.to_lowercase().as_str() {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "space" => KeyCode::Char(' '),
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => return None,
+    })
+}
+
+/// Parses key names like `q`, `A`, `enter`, `ctrl-r` or `alt-backspace`.
+/// `shift-a` is read as `A`, which is what the terminal sends for it.
+pub fn parse_key(spec: &str) -> Result<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = spec;
+    while let Some((m, r)) = rest.split_once('-').filter(|(_, r)| !r.is_empty()) {
+        modifiers |= match m.to_lowercase().as_str() {
+            "ctrl" | "c" => KeyModifiers::CONTROL,
+            "alt" | "a" | "m" => KeyModifiers::ALT,
+            "shift" | "s" => KeyModifiers::SHIFT,
+            _ => anyhow::bail!("unknown modifier '{m}' in key '{spec}'"),
+        };
+        rest = r;
+    }
+
+    let code = match named_key(rest) {
+        Some(code) => code,
+        None => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None)
+                    if c.is_lowercase() && modifiers.contains(KeyModifiers::SHIFT) =>
+                {
+                    modifiers -= KeyModifiers::SHIFT;
+                    KeyCode::Char(c.to_uppercase().next().unwrap_or(c))
+                }
+                (Some(c), None) => KeyCode::Char(c),
+                _ => anyhow::bail!("unknown key '{spec}'"),
+            }
+        }
+    };
+    Ok(KeyEvent::new(code, modifiers))
+}
+
+/// Parses a sequence of space separated keys such as `ctrl-x s`. A run of
+/// plain characters like `gg` is read as one key per character.
+pub fn parse_sequence(spec: &str) -> Result<Vec<KeyEvent>> {
+    let mut seq = vec![];
+    for part in spec.split_whitespace() {
+        if part.contains('-') || named_key(part).is_some() {
+            seq.push(parse_key(part)?);
+        } else {
+            seq.extend(
+                part.chars()
+                    .map(|c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)),
+            );
+        }
+    }
+    if seq.is_empty() {
+        anyhow::bail!("empty key");
+    }
+    Ok(seq)
+}
+
+fn format_key(key: &KeyEvent) -> String {
+    let mut s = String::new();
+    if key.modifiers.contains(KeyModifiers::CONTROL) {
+        s.push_str("ctrl-");
+    }
+    if key.modifiers.contains(KeyModifiers::ALT) {
+        s.push_str("alt-");
+    }
+    match key.code {
+        KeyCode::Char(' ') => s.push_str("space"),
+        KeyCode::Char(c) => s.push(c),
+        KeyCode::BackTab => s.push_str("backtab"),
+        KeyCode::PageUp => s.push_str("pageup"),
+        KeyCode::PageDown => s.push_str("pagedown"),
+        code => s.push_str(&code.to_string().to_lowercase()),
+    }
+    s
+}
+
+pub fn format_sequence(seq: &[KeyEvent]) -> String {
+    let keys: Vec<String> = seq.iter().map(format_key).collect();
+    if seq.iter().all(|k| {
+        matches!(k.code, KeyCode::Char(c) if c != ' ') && k.modifiers.is_empty()
+    }) {
+        keys.concat()
+    } else {
+        keys.join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::NONE)
+    }
+
+    fn keymap(overrides: &[(&str, &str)]) -> Result<Keymap> {
+        let overrides = overrides
+            .iter()
+            .map(|(name, spec)| (name.to_string(), KeySpec::One(spec.to_string())))
+            .collect();
+        Keymap::new(&overrides)
+    }
+
+    #[test]
+    fn resolves_multi_key_sequences() {
+        let keymap = Keymap::default();
+        let mut pending = vec![];
+        assert_eq!(keymap.resolve(&mut pending, key('g')), Resolved::Pending);
+        assert_eq!(pending, [key('g')]);
+        assert_eq!(
+            keymap.resolve(&mut pending, key('g')),
+            Resolved::Action(Action::FirstItem)
+        );
+        assert!(pending.is_empty());
+
+        assert_eq!(keymap.resolve(&mut pending, key('d')), Resolved::Pending);
+        assert_eq!(
+            keymap.resolve(&mut pending, key('d')),
+            Resolved::Action(Action::Delete)
+        );
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn key_breaking_a_sequence_is_tried_on_its_own() {
+        let keymap = Keymap::default();
+        let mut pending = vec![];
+        keymap.resolve(&mut pending, key('g'));
+        assert_eq!(
+            keymap.resolve(&mut pending, key('j')),
+            Resolved::Action(Action::NextItem)
+        );
+        assert!(pending.is_empty());
+
+        keymap.resolve(&mut pending, key('d'));
+        assert_eq!(keymap.resolve(&mut pending, key('x')), Resolved::Unbound);
+        assert!(pending.is_empty());
+    }
+
+    #[test]
+    fn rejects_bindings_shadowed_by_a_prefix() {
+        let err = keymap(&[("quit", "g")]).unwrap_err();
+        assert!(err.to_string().contains("shadows"), "{err}");
+        assert!(keymap(&[("quit", "ddd")]).is_err());
+        assert!(keymap(&[("quit", "j")]).is_err());
+        assert!(keymap(&[("quit", "ZZ")]).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_actions_and_keys() {
+        assert!(keymap(&[("fly", "f")]).is_err());
+        assert!(keymap(&[("quit", "hyper-q")]).is_err());
+        assert!(keymap(&[("quit", "ctrl-nope")]).is_err());
+    }
+
+    #[test]
+    fn parses_modifiers() {
+        let ctrl_r = parse_key("ctrl-r").unwrap();
+        assert_eq!(
+            ctrl_r,
+            KeyEvent::new(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        );
+        let alt_bs = parse_key("alt-backspace").unwrap();
+        assert_eq!(alt_bs, KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT));
+        let both = parse_key("ctrl-alt-x").unwrap();
+        assert_eq!(both.modifiers, KeyModifiers::CONTROL | KeyModifiers::ALT);
+        assert_eq!(parse_key("C-x").unwrap().modifiers, KeyModifiers::CONTROL);
+        assert_eq!(parse_key("-").unwrap().code, KeyCode::Char('-'));
+        assert_eq!(parse_key("ctrl--").unwrap().code, KeyCode::Char('-'));
+    }
+
+    #[test]
+    fn shift_letter_matches_the_uppercase_key() {
+        let bound = parse_key("shift-a").unwrap();
+        assert_eq!(bound, key('A'));
+        let sent = KeyEvent::new(KeyCode::Char('A'), KeyModifiers::SHIFT);
+        assert!(key_matches(&bound, &sent));
+        assert!(!key_matches(&bound, &key('a')));
+
+        let bound = parse_key("ctrl-shift-a").unwrap();
+        let sent = KeyEvent::new(
+            KeyCode::Char('A'),
+            KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+        );
+        assert!(key_matches(&bound, &sent));
+        assert_eq!(format_key(&bound), "ctrl-A");
+    }
+
+    #[test]
+    fn sequences_round_trip_through_formatting() {
+        for spec in ["gg", "ctrl-x s", "space", "alt-enter"] {
+            assert_eq!(format_sequence(&parse_sequence(spec).unwrap()), spec);
+        }
+    }
+}
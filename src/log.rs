@@ -1,195 +1,597 @@
-use crate::{data::SaveData, tab::Tab, tag::TagSys, theme};
+use crate::{
+    data::SaveData,
+    keymap::{Action, Keymap},
+    tab::Tab,
+    tag::{self, TagSys},
+    theme,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
 use ratatui::{
     Frame,
-    crossterm::event::{self, KeyCode},
     layout::{Constraint, Flex, Layout, Rect},
     style::{Color, Style, Stylize},
     text::{Line, Span, ToSpan},
-    widgets::{Block, List, ListItem, ListState, Paragraph, Widget},
+    widgets::{Block, BorderType, List, ListItem, ListState, Paragraph, Widget, Wrap},
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use std::{
-    collections::HashSet,
-    time::{Duration, Instant},
-};
+use std::collections::HashSet;
 use tracing::info;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Log {
+    /// Stays the same for the life of the log, so copies of it in other
+    /// processes, the journal and the database can be matched up.
+    pub id: String,
     pub done: bool,
     pub name: String,
-    #[serde(with = "serde_millis")]
-    pub start: Instant,
-    #[serde(with = "serde_millis")]
-    pub end: Instant,
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
     pub tags: HashSet<String>,
+    pub segments: Vec<Segment>,
+    pub paused: bool,
+    /// Free-form, possibly multi-line description of the work done.
+    pub notes: String,
+}
+
+/// A span of time during which a log was actively being worked on.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Segment {
+    pub start: DateTime<Local>,
+    pub end: DateTime<Local>,
 }
 
 pub struct LogList {
     title: String,
+    kind: LogType,
     pub state: ListState,
+    /// Whether the detail pane of the selected log is open.
+    detail: bool,
+    /// Shown instead of the list when there are no logs.
+    empty_msg: String,
 }
 
 impl LogList {
-    pub fn new(title: &str) -> Self {
+    pub fn new(title: &str, kind: LogType, keymap: &Keymap) -> Self {
         let t = format!("| {} |", title);
+        let empty_msg = match (kind, keymap.keys_for(Action::NewLog).first()) {
+            (LogType::Past, _) => "No completed logs.".to_string(),
+            (LogType::Active, Some(key)) => {
+                format!("No active logs.\n Start by creating a log with {key}.")
+            }
+            (LogType::Active, None) => "No active logs.".to_string(),
+        };
         Self {
             title: t,
+            kind,
             state: ListState::default(),
+            detail: false,
+            empty_msg,
         }
     }
 
-    pub fn delete_selected(self: &Self, data: &mut SaveData) {
-        if let Some(i) = self.state.selected() {
-            let log = &data.logs[i];
-            log.tags.iter().for_each(|t| {
-                data.tags.rm_ref(t);
-            });
+    fn logs<'a>(&self, data: &'a SaveData) -> &'a Vec<Log> {
+        match self.kind {
+            LogType::Active => &data.logs,
+            LogType::Past => &data.past_logs,
+        }
+    }
+
+    pub fn delete_selected(&self, data: &mut SaveData) -> Option<String> {
+        let i = self.state.selected()?;
+        let logs = match self.kind {
+            LogType::Active => &mut data.logs,
+            LogType::Past => &mut data.past_logs,
+        };
+        if i >= logs.len() {
+            return None;
+        }
+
+        let log = logs.remove(i);
+        log.tags.iter().for_each(|t| {
+            data.tags.rm_ref(t);
+        });
+        info!("Deleted log ({:?})", self.kind);
+        Some(format!("Deleted '{}'", log.name))
+    }
 
-            data.logs.remove(i);
-            info!("{}", "Deleted active log");
+    pub fn complete_selected(&self, data: &mut SaveData) -> Option<String> {
+        if self.kind != LogType::Active {
+            return None;
         }
+        let log = complete_log(data, self.state.selected()?)?;
+        Some(format!("Completed '{}'", log.name))
     }
 
-    pub fn handle_add(input: String, data: &mut SaveData) {
-        let (name, tags) = parse_input(input);
+    pub fn toggle_pause_selected(&self, data: &mut SaveData) -> Option<String> {
+        if self.kind != LogType::Active {
+            return None;
+        }
+        let log = self.state.selected().and_then(|i| data.logs.get_mut(i))?;
+        if log.paused {
+            log.resume();
+            Some(format!("Resumed '{}'", log.name))
+        } else {
+            log.pause();
+            Some(format!("Paused '{}'", log.name))
+        }
+    }
+
+    pub fn handle_add(
+        input: String,
+        data: &mut SaveData,
+        default_tags: &[String],
+    ) -> String {
+        let (name, mut tags) = parse_input(input);
+        for t in default_tags {
+            if !tags.contains(t) {
+                tags.push(t.clone());
+            }
+        }
         tags.iter().for_each(|t| data.tags.add(t).refs += 1);
+        let desc = format!("Added '{name}'");
         data.logs.push(Log::new(name, HashSet::from_iter(tags)));
+        desc
     }
 }
 
 impl Tab for LogList {
     fn render(
-        self: &mut Self,
+        &mut self,
         blk: &Block,
         area: &Rect,
         frame: &mut Frame,
         data: &mut SaveData,
     ) {
-        {
-            if data.logs.is_empty() {
-                render_empty_msg(frame, blk, area, false);
-                return;
-            }
+        let logs = self.logs(data);
+        if logs.is_empty() {
+            render_empty_msg(frame, blk, area, &self.empty_msg);
+            return;
         }
 
         let list = {
-            List::new(data.logs.iter().map(|l| {
-                let v = l.name.to_span().fg(theme::TEXT);
+            List::new(logs.iter().map(|l| {
+                let (name_color, dur_color) = if l.paused {
+                    (theme::text_alt(), theme::yellow())
+                } else {
+                    (theme::text(), theme::blue())
+                };
+                let v = l.name.to_span().fg(name_color);
                 let mut dur_str = String::from(" ");
-                dur_str.push_str(&duration_as_hhmmss(l.end.duration_since(l.start)));
+                dur_str.push_str(&duration_as_hhmmss(l.duration()));
 
-                let dur = Span::styled(dur_str, Style::default().fg(theme::BLUE));
+                let dur = Span::styled(dur_str, Style::default().fg(dur_color));
 
                 let mut vec = vec![v, dur];
+                if l.paused {
+                    vec.insert(0, theme::unicode_icon(0x23f8, theme::yellow()));
+                    vec.push(Span::styled(" (paused)", theme::yellow()).italic());
+                }
+                if self.kind == LogType::Past {
+                    let date = l.start.format(" %Y-%m-%d %H:%M").to_string();
+                    vec.push(Span::styled(date, theme::text_alt()));
+                }
                 let mut tag_txt = get_log_tag_text(l, &data.tags);
                 vec.append(&mut tag_txt);
 
                 let ln = Line::from(vec);
-                let color = theme::BG0;
+                let color = theme::bg0();
                 ListItem::from(ln).bg(color)
             }))
             .block(blk.clone())
-            .bg(theme::BG0)
-            .highlight_style(Style::default().bg(theme::BG1))
+            .bg(theme::bg0())
+            .highlight_style(Style::default().bg(theme::bg1()))
             .highlight_symbol("> ")
         };
 
-        frame.render_stateful_widget(list, *area, &mut self.state);
+        let selected = self.state.selected().and_then(|i| logs.get(i));
+        let (list_area, detail) = match selected {
+            Some(log) if self.detail => {
+                let [list_area, detail_area] = Layout::horizontal([
+                    Constraint::Percentage(55),
+                    Constraint::Fill(1),
+                ])
+                .areas(*area);
+                (list_area, Some((log, detail_area)))
+            }
+            _ => (*area, None),
+        };
+        if let Some((log, detail_area)) = detail {
+            render_detail(frame, log, &data.tags, &detail_area);
+        }
+        frame.render_stateful_widget(list, list_area, &mut self.state);
     }
 
-    fn get_title<'a>(self: &'a Self) -> &'a str {
+    fn get_title(&self) -> &str {
         &self.title
     }
 
-    fn get_line(self: &Self) -> Line<'static> {
-        let icon = theme::unicode_icon(0xf02c, theme::BLUE);
-        let name = Span::raw("Logs");
-        Line::from(vec![icon, name])
+    fn get_line(&self) -> Line<'static> {
+        let (color, name) = match self.kind {
+            LogType::Active => (theme::blue(), "Logs"),
+            LogType::Past => (theme::aqua(), "Past Logs"),
+        };
+        let icon = theme::unicode_icon(0xf02c, color);
+        Line::from(vec![icon, Span::raw(name)])
+    }
+
+    fn actions(&self) -> &'static [(Action, &'static str)] {
+        match self.kind {
+            LogType::Active => &[
+                (Action::NewLog, "new log"),
+                (Action::Complete, "complete"),
+                (Action::Pause, "pause / resume"),
+                (Action::Open, "details"),
+                (Action::EditLog, "edit"),
+                (Action::EditNotes, "edit notes"),
+                (Action::Delete, "delete"),
+                (Action::NextItem, "next"),
+                (Action::PrevItem, "previous"),
+                (Action::FirstItem, "first"),
+                (Action::LastItem, "last"),
+            ],
+            LogType::Past => &[
+                (Action::Complete, "details"),
+                (Action::Open, "details"),
+                (Action::EditLog, "edit"),
+                (Action::EditNotes, "edit notes"),
+                (Action::Delete, "delete"),
+                (Action::NextItem, "next"),
+                (Action::PrevItem, "previous"),
+                (Action::FirstItem, "first"),
+                (Action::LastItem, "last"),
+            ],
+        }
     }
 
-    fn handle_keys(self: &mut Self, key: KeyCode, data: &mut SaveData) {
-        match key {
-            event::KeyCode::Char(char) => match char {
-                'D' => {
-                    self.delete_selected(data);
-                }
-                'n' | 'j' => {
-                    self.state.select_next();
-                }
-                'm' | 'k' => {
-                    self.state.select_previous();
-                }
+    fn confirm_msg(&self, action: Action, data: &SaveData) -> String {
+        let log = self.state.selected().and_then(|i| self.logs(data).get(i));
+        match (action, log) {
+            (Action::Delete, Some(log)) => format!("Delete '{}'?", log.name),
+            (Action::Complete, Some(log)) => format!("Complete '{}'?", log.name),
+            _ => format!("Really {}?", action.name()),
+        }
+    }
 
-                _ => {}
-            },
+    fn handle_action(&mut self, action: Action, data: &mut SaveData) -> Option<String> {
+        match action {
+            Action::Delete => return self.delete_selected(data),
+            Action::Pause => return self.toggle_pause_selected(data),
+            Action::Complete if self.kind == LogType::Past => self.detail = !self.detail,
+            Action::Complete => return self.complete_selected(data),
+            Action::Open => self.detail = !self.detail,
+            Action::NextItem => self.state.select_next(),
+            Action::PrevItem => self.state.select_previous(),
+            Action::FirstItem => self.state.select_first(),
+            Action::LastItem => self.state.select_last(),
             _ => {}
         }
+        None
+    }
+
+    fn selected(&self) -> Option<usize> {
+        self.state.selected()
+    }
+
+    fn selection_id(&self, data: &SaveData) -> Option<String> {
+        let log = self.logs(data).get(self.state.selected()?)?;
+        Some(log.id.clone())
+    }
+
+    fn reselect(&mut self, id: &str, data: &SaveData) -> bool {
+        let logs = self.logs(data);
+        let found = logs.iter().position(|l| l.id == id);
+        let i = found
+            .or_else(|| Some(self.state.selected()?.min(logs.len().checked_sub(1)?)));
+        self.state.select(i);
+        found.is_some()
     }
 }
 
+/// A random (version 4) UUID.
+pub fn new_id() -> String {
+    let mut bytes = fastrand::u128(..).to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
 impl Log {
     pub fn new(desc: String, tags: HashSet<String>) -> Self {
+        let now = Local::now();
         Self {
-            start: Instant::now(),
-            end: Instant::now(),
+            id: new_id(),
+            start: now,
+            end: now,
             done: false,
             name: desc,
             tags,
+            segments: vec![Segment {
+                start: now,
+                end: now,
+            }],
+            paused: false,
+            notes: String::new(),
+        }
+    }
+
+    /// Total time spent in active segments.
+    pub fn duration(&self) -> TimeDelta {
+        if self.segments.is_empty() {
+            return self.end - self.start;
+        }
+        self.segments.iter().map(|s| s.end - s.start).sum()
+    }
+
+    /// Logs saved before segments existed are treated as a single segment.
+    pub fn ensure_segments(&mut self) {
+        if self.segments.is_empty() {
+            self.segments.push(Segment {
+                start: self.start,
+                end: self.end,
+            });
         }
     }
+
+    /// Extends the running segment (and the log) up to `now`.
+    pub fn tick(&mut self, now: DateTime<Local>) {
+        self.ensure_segments();
+        self.end = now;
+        if let Some(seg) = self.segments.last_mut() {
+            seg.end = now;
+        }
+    }
+
+    /// Moves the log to `[start, end)`, dropping segments outside of it
+    /// and stretching or clipping the first and last ones to fit.
+    pub fn set_span(&mut self, start: DateTime<Local>, end: DateTime<Local>) {
+        self.ensure_segments();
+        self.segments.retain(|s| s.end > start && s.start < end);
+        if self.segments.is_empty() {
+            self.segments.push(Segment { start, end });
+        }
+        if let Some(first) = self.segments.first_mut() {
+            first.start = start;
+        }
+        if let Some(last) = self.segments.last_mut() {
+            last.end = end;
+        }
+        self.start = start;
+        self.end = end;
+    }
+
+    pub fn pause(&mut self) {
+        self.tick(Local::now());
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        let now = Local::now();
+        self.ensure_segments();
+        self.segments.push(Segment {
+            start: now,
+            end: now,
+        });
+        self.end = now;
+        self.paused = false;
+    }
 }
 
-/*pub fn delete_past_log(self: &Self, data: &mut SaveData) {
-    if let Some(i) = self.list_state.selected() {
-        data.past_logs.remove(i);
-        info!("{}", "Deleted old log");
+/// Moves the active log at `i` into the past logs.
+pub fn complete_log(data: &mut SaveData, i: usize) -> Option<&Log> {
+    if i >= data.logs.len() {
+        return None;
+    }
+    let mut log = data.logs.remove(i);
+    if !log.paused {
+        log.tick(Local::now());
     }
-}*/
+    log.done = true;
+    log.paused = false;
+    data.past_logs.push(log);
+    data.past_logs.last()
+}
+
+/// Parses a point in time for the edit form: relative to `now` (`-15m`,
+/// `+1h30m`), a time of day on the date of `reference` (`09:30`) or a full
+/// `YYYY-MM-DD HH:MM`.
+pub fn parse_when(
+    input: &str,
+    reference: DateTime<Local>,
+    now: DateTime<Local>,
+) -> Result<DateTime<Local>> {
+    let input = input.trim();
+    let relative = Regex::new(r"^([+-])(?:(\d+)h)?(?:(\d+)m)?$").unwrap();
+    if let Some(c) = relative.captures(input)
+        && (c.get(2).is_some() || c.get(3).is_some())
+    {
+        let num = |i: usize| c.get(i).map_or(Ok(0), |m| m.as_str().parse::<i64>());
+        let delta = TimeDelta::hours(num(2)?) + TimeDelta::minutes(num(3)?);
+        return Ok(if &c[1] == "-" {
+            now - delta
+        } else {
+            now + delta
+        });
+    }
+
+    let naive = if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        reference.date_naive().and_time(time)
+    } else {
+        NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M")
+            .or_else(|_| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S"))
+            .with_context(|| format!("invalid time '{input}'"))?
+    };
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .with_context(|| format!("'{input}' does not exist in the local timezone"))
+}
 
-fn parse_input(input: String) -> (String, Vec<String>) {
-    let regex = Regex::new(r"(tag:\s(\w+))+").unwrap();
-    let matches: Vec<&str> = regex.find_iter(&input).map(|m| m.as_str()).collect();
-    let mut cpy = input.clone();
+/// Applies the edit form to log `i` of the active or past logs. Returns
+/// the names of other logs the new times overlap with.
+pub fn apply_edit(
+    data: &mut SaveData,
+    past: bool,
+    i: usize,
+    name: &str,
+    tags: &str,
+    start: &str,
+    end: Option<&str>,
+) -> Result<Vec<String>> {
+    let now = Local::now();
+    let logs = if past { &data.past_logs } else { &data.logs };
+    let log = logs.get(i).context("log no longer exists")?;
 
-    for m in &matches {
-        cpy = cpy.replace(m, "");
+    let name = name.trim();
+    anyhow::ensure!(!name.is_empty(), "name can't be empty");
+    let tag_re = Regex::new(&format!("^{}$", tag::NAME_PATTERN)).unwrap();
+    if let Some(t) = tags.split_whitespace().find(|t| !tag_re.is_match(t)) {
+        anyhow::bail!("invalid tag '{t}'");
     }
+    let tags: HashSet<String> = tags.split_whitespace().map(tag::normalize).collect();
 
-    (
-        cpy.trim().to_owned(),
-        matches
-            .iter()
-            .map(|m| m.to_string().replace("tag: ", ""))
-            .collect(),
-    )
+    let start = parse_when(start, log.start, now)?;
+    let end = match end {
+        Some(end) => parse_when(end, log.end, now)?,
+        None => log.end.max(start),
+    };
+    anyhow::ensure!(
+        end > start || (!past && end == start),
+        "end must be after start"
+    );
+    anyhow::ensure!(past || start <= now, "start can't be in the future");
+
+    let overlaps = data
+        .logs
+        .iter()
+        .enumerate()
+        .filter(|(j, _)| past || *j != i)
+        .chain(
+            data.past_logs
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| !past || *j != i),
+        )
+        .map(|(_, l)| l)
+        .filter(|l| l.start < end && start < l.end)
+        .map(|l| l.name.clone())
+        .collect();
+
+    let logs = if past {
+        &mut data.past_logs
+    } else {
+        &mut data.logs
+    };
+    let log = &mut logs[i];
+    log.name = name.to_string();
+    log.tags = tags;
+    log.set_span(start, end);
+
+    let SaveData {
+        logs,
+        past_logs,
+        tags,
+        ..
+    } = data;
+    tags.rebuild_refs(logs.iter().chain(past_logs.iter()));
+    Ok(overlaps)
+}
+
+pub fn parse_input(input: String) -> (String, Vec<String>) {
+    let regex = Regex::new(&format!(r"tag:\s({})", tag::NAME_PATTERN)).unwrap();
+    let tags = regex
+        .captures_iter(&input)
+        .map(|c| tag::normalize(&c[1]))
+        .collect();
+    let name = regex.replace_all(&input, "");
+    (name.split_whitespace().collect::<Vec<_>>().join(" "), tags)
 }
 
 fn get_log_tag_text<'a>(log: &'a Log, sys: &'a TagSys) -> Vec<Span<'a>> {
     let mut spans: Vec<Span> = Vec::new();
     for t in &log.tags {
         let str = String::from(" ") + t;
-        let tag = sys.tags().iter().find(|e| e.name() == t).unwrap();
-        let color = Color::from_u32(tag.color().clone());
+        let color = sys
+            .tags()
+            .iter()
+            .find(|e| e.name() == t)
+            .map(|tag| Color::from_u32(*tag.color()))
+            .unwrap_or(theme::text_alt());
         spans.push(Span::styled(str, Style::default().fg(color).bold()));
     }
     spans
 }
 
-#[derive(PartialEq, PartialOrd, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq)]
 pub enum LogType {
     Active,
     Past,
 }
 
-fn render_empty_msg(frame: &mut Frame, block: &Block, outer: &Rect, old: bool) {
-    let msg = if old {
-        "No completed logs."
+fn render_detail(frame: &mut Frame, log: &Log, tags: &TagSys, area: &Rect) {
+    let label = |s: &str| Span::styled(format!("{s:<10}"), theme::text_alt());
+    let time = |t: &DateTime<Local>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+    let end = if log.done {
+        time(&log.end)
+    } else if log.paused {
+        "paused".to_string()
     } else {
-        "No active logs.\n Start by creating a log with <S-A>."
+        "running".to_string()
     };
 
+    let mut lines = vec![
+        Line::from(log.name.clone()).fg(theme::text()).bold(),
+        Line::default(),
+        Line::from([vec![label("Tags")], get_log_tag_text(log, tags)].concat()),
+        Line::from(vec![label("Start"), Span::raw(time(&log.start))]),
+        Line::from(vec![label("End"), Span::raw(end)]),
+        Line::from(vec![
+            label("Duration"),
+            Span::styled(duration_as_hhmmss(log.duration()), theme::blue()),
+        ]),
+        Line::default(),
+        Line::from("Segments").fg(theme::orange()),
+    ];
+    for seg in &log.segments {
+        lines.push(Line::from(vec![
+            Span::raw(format!(
+                "  {} - {}  ",
+                seg.start.format("%m-%d %H:%M"),
+                seg.end.format("%H:%M")
+            )),
+            Span::styled(duration_as_hhmmss(seg.end - seg.start), theme::text_alt()),
+        ]));
+    }
+    lines.push(Line::default());
+    lines.push(Line::from("Notes").fg(theme::orange()));
+    if log.notes.is_empty() {
+        lines.push(Line::from("  none").fg(theme::text_alt()).italic());
+    }
+    lines.extend(log.notes.lines().map(|l| Line::from(format!("  {l}"))));
+
+    Paragraph::new(lines)
+        .block(
+            Block::bordered()
+                .border_type(BorderType::Rounded)
+                .fg(theme::blue())
+                .bg(theme::bg0())
+                .title("| Details |".to_span().into_centered_line()),
+        )
+        .fg(theme::text())
+        .wrap(Wrap { trim: false })
+        .render(*area, frame.buffer_mut());
+}
+
+fn render_empty_msg(frame: &mut Frame, block: &Block, outer: &Rect, msg: &str) {
     let area = {
         let vert = Layout::vertical([Constraint::Percentage(8)]).flex(Flex::Center);
         let horz = Layout::horizontal([Constraint::Percentage(50)]).flex(Flex::Center);
@@ -201,14 +603,14 @@ fn render_empty_msg(frame: &mut Frame, block: &Block, outer: &Rect, old: bool) {
     block.render(*outer, frame.buffer_mut());
 
     Paragraph::new(msg)
-        .fg(theme::TEXT_ALT)
-        .bg(theme::BG0)
+        .fg(theme::text_alt())
+        .bg(theme::bg0())
         .centered()
         .render(area, frame.buffer_mut());
 }
 
-fn duration_as_hhmmss(dur: Duration) -> String {
-    let total_seconds = dur.as_secs();
+pub fn duration_as_hhmmss(dur: TimeDelta) -> String {
+    let total_seconds = dur.num_seconds().max(0);
     let hours = total_seconds / 3600;
     let minutes = (total_seconds % 3600) / 60;
     let seconds = total_seconds % 60;
@@ -218,7 +620,119 @@ fn duration_as_hhmmss(dur: Duration) -> String {
 
 // TODO: Optimize
 pub fn update_logs(logs: &mut [Log]) {
-    for log in logs.iter_mut() {
-        log.end = Instant::now();
+    let now = Local::now();
+    for log in logs.iter_mut().filter(|l| !l.paused) {
+        log.tick(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 1, day, hour, min, 0).unwrap()
+    }
+
+    fn past_log(name: &str, start: DateTime<Local>, end: DateTime<Local>) -> Log {
+        let mut log = Log::new(name.into(), HashSet::new());
+        log.set_span(start, end);
+        log.done = true;
+        log
+    }
+
+    fn data() -> SaveData {
+        let mut data = SaveData::new();
+        data.past_logs = vec![
+            past_log("standup", at(6, 9, 0), at(6, 10, 0)),
+            past_log("review", at(6, 11, 0), at(6, 12, 0)),
+        ];
+        data
+    }
+
+    #[test]
+    fn parses_times_relative_to_now() {
+        let (reference, now) = (at(5, 8, 0), at(6, 12, 0));
+        let when = |s| parse_when(s, reference, now).unwrap();
+        assert_eq!(when("-15m"), at(6, 11, 45));
+        assert_eq!(when("+1h30m"), at(6, 13, 30));
+        assert_eq!(when("-2h"), at(6, 10, 0));
+        assert_eq!(when(" +0m "), now);
+    }
+
+    #[test]
+    fn parses_times_of_day_and_full_dates() {
+        let (reference, now) = (at(5, 8, 0), at(6, 12, 0));
+        let when = |s| parse_when(s, reference, now).unwrap();
+        assert_eq!(when("09:30"), at(5, 9, 30));
+        assert_eq!(when("2025-01-03 17:05"), at(3, 17, 5));
+        assert_eq!(when("2025-01-03 17:05:00"), at(3, 17, 5));
+    }
+
+    #[test]
+    fn rejects_invalid_times() {
+        let now = at(6, 12, 0);
+        for input in [
+            "",
+            "-",
+            "+h",
+            "15m",
+            "-15x",
+            "25:00",
+            "9.30",
+            "2025-13-01 10:00",
+        ] {
+            assert!(parse_when(input, now, now).is_err(), "{input}");
+        }
+    }
+
+    #[test]
+    fn edits_name_tags_and_times() {
+        let mut data = data();
+        let overlaps = apply_edit(
+            &mut data,
+            true,
+            1,
+            " retro ",
+            "team/x",
+            "13:00",
+            Some("14:15"),
+        )
+        .unwrap();
+        assert!(overlaps.is_empty());
+        let log = &data.past_logs[1];
+        assert_eq!(log.name, "retro");
+        assert_eq!(log.tags, HashSet::from(["team:x".to_string()]));
+        assert_eq!((log.start, log.end), (at(6, 13, 0), at(6, 14, 15)));
+        assert_eq!(log.duration(), TimeDelta::minutes(75));
+        assert_eq!(data.tags.tags()[0].refs, 1);
+    }
+
+    #[test]
+    fn warns_about_overlapping_logs() {
+        let mut data = data();
+        let overlaps =
+            apply_edit(&mut data, true, 1, "review", "", "09:30", None).unwrap();
+        assert_eq!(overlaps, ["standup"]);
+        assert_eq!(data.past_logs[1].start, at(6, 9, 30));
+
+        // touching logs don't overlap
+        let overlaps =
+            apply_edit(&mut data, true, 1, "review", "", "10:00", None).unwrap();
+        assert!(overlaps.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_edits() {
+        let mut data = data();
+        let mut edit = |name, tags, start, end| {
+            apply_edit(&mut data, true, 1, name, tags, start, end).is_err()
+        };
+        assert!(edit(" ", "", "11:00", None));
+        assert!(edit("review", "not!a:tag", "11:00", None));
+        assert!(edit("review", "", "12:30", Some("12:00")));
+        assert!(edit("review", "", "soon", None));
+        assert_eq!(data.past_logs[1].start, at(6, 11, 0));
+        assert!(apply_edit(&mut data, true, 5, "review", "", "11:00", None).is_err());
     }
 }
@@ -1,6 +1,22 @@
 mod animation;
+mod cli;
+mod complete;
+mod config;
+mod daemon;
 mod data;
+mod editor;
+mod export;
+mod form;
+mod help;
+mod history;
+mod import;
+mod journal;
+mod keymap;
 mod log;
+mod migrate;
+mod modal;
+mod report;
+mod storage;
 mod tab;
 mod tag;
 mod theme;
@@ -10,19 +26,40 @@ use animation::{AnimationHandler, add_anim_if_missing, after_anim};
 #[allow(unused_imports)]
 use tracing::{info, warn};
 
-use crate::{data::SaveData, log::LogList, tab::Tab, tag::*};
+use crate::{
+    complete::Completion,
+    config::Config,
+    data::SaveData,
+    editor::LineEditor,
+    form::{Form, FormResult},
+    history::{History, Snapshot},
+    keymap::{Action, Resolved},
+    log::{LogList, LogType},
+    modal::{Modal, ModalKind, ModalResult},
+    report::ReportTab,
+    tab::Tab,
+    tag::TagList,
+};
 use anyhow::{Ok, Result};
-use dirs::config_dir;
+use clap::Parser;
 use ratatui::{
     DefaultTerminal, Frame,
-    crossterm::event::{self, Event, KeyEvent},
-    layout::{Constraint, Flex, Layout, Rect},
+    crossterm::{
+        ExecutableCommand,
+        event::{self, DisableBracketedPaste, EnableBracketedPaste, Event, KeyEvent},
+    },
+    layout::{Constraint, Flex, Layout, Position, Rect},
     style::Stylize,
     text::{Line, Span, ToSpan},
-    widgets::{Block, BorderType, Clear, ListState, Paragraph, Widget},
+    widgets::{Block, BorderType, Clear, Paragraph, Widget},
 };
 use regex::Regex;
-use std::{cell::RefCell, fs};
+use std::{
+    cell::RefCell,
+    io::{Write, stdout},
+    os::unix::fs::{DirBuilderExt, OpenOptionsExt},
+    path::PathBuf,
+};
 use std::{
     collections::HashMap,
     time::{Duration, Instant},
@@ -33,99 +70,147 @@ use tachyonfx::{
 };
 use tracing_subscriber::FmtSubscriber;
 
+const TOAST_DURATION: Duration = Duration::from_secs(3);
+/// How often the data file is checked for changes made by other processes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What the text submitted from the input dialog is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputTarget {
+    /// Handled by the focused tab.
+    Tab,
+    Export,
+    MergeTag,
+}
+
 struct State {
-    list_state: ListState,
     input_dialog_active: bool,
-    popup_active: bool,
-    popup_msg: Span<'static>,
-    input: String,
+    input_target: InputTarget,
+    modal: Option<Modal>,
+    help_active: bool,
+    /// Short-lived message shown over the hint bar.
+    toast: Option<(String, Instant)>,
+    input: LineEditor,
+    /// Keys typed so far of an unfinished key sequence.
+    pending_keys: Vec<KeyEvent>,
+    completion: Completion,
     input_default: (&'static str, &'static str),
     input_display: Line<'static>,
-    main_panel_title: &'static str,
     anims: RefCell<AnimationHandler>,
     focused_list_idx: usize,
     focused_list: tab::ListType,
     rendered_lists: Vec<Box<dyn Tab>>,
     dt: f64,
     opened_once: bool,
+    config: Config,
+    history: History,
+    /// Log whose notes should be opened in `$EDITOR` by the run loop.
+    edit_notes: Option<(tab::ListType, usize)>,
+    /// Open edit form and the log it edits.
+    form: Option<(Form, (tab::ListType, usize))>,
 }
 
 impl State {
-    pub fn update_input_display(&mut self) {
-        let regex = Regex::new(r"(tag:\s(\w+))+$").unwrap();
+    fn show_popup(&mut self, msg: Span<'static>) {
+        self.modal = Some(Modal::message(msg));
+    }
 
-        self.input_display = if regex.is_match(&self.input) {
-            let index = self.input.find(" tag: ").unwrap();
+    fn show_toast(&mut self, msg: String) {
+        self.toast = Some((msg, Instant::now()));
+    }
 
-            Line::from(vec![
-                Span::styled(self.input[..index].to_string(), theme::TEXT),
-                Span::styled(self.input[index..].to_string(), theme::ORANGE),
-            ])
+    pub fn update_input_display(&mut self, data: &SaveData) {
+        let regex = Regex::new(&format!(r"tag:\s({})", tag::NAME_PATTERN)).unwrap();
+
+        let input = self.input.text();
+        let mut spans = vec![];
+        let mut last = 0;
+        for cap in regex.captures_iter(input) {
+            let (all, name) = (cap.get(0).unwrap(), cap.get(1).unwrap());
+            spans.push(Span::styled(
+                input[last..all.start()].to_string(),
+                theme::text(),
+            ));
+            spans.push(Span::styled(
+                input[all.start()..name.start()].to_string(),
+                theme::orange(),
+            ));
+            let known = data.tags.tags().iter().any(|t| t.name() == name.as_str());
+            spans.push(if known {
+                Span::styled(name.as_str().to_string(), theme::orange())
+            } else {
+                Span::styled(name.as_str().to_string(), theme::yellow()).italic()
+            });
+            last = all.end();
+        }
+        spans.push(Span::styled(input[last..].to_string(), theme::text()));
+        self.input_display = Line::from(spans);
+
+        if self.input_target == InputTarget::Tab
+            && self.focused_list == tab::ListType::Log
+        {
+            self.completion.update(self.input.before_cursor(), data);
         } else {
-            Line::from(vec![Span::raw(self.input.clone())])
-        };
+            self.completion.clear();
+        }
     }
 }
 
 fn main() -> Result<()> {
-    let file_appender = tracing_appender::rolling::daily(
-        "/home/astro/projects/kairotui/logs",
-        "kairotui.log",
-    );
+    let args = cli::Cli::parse();
+    let (config, config_err) = Config::load();
+
+    let file_appender =
+        tracing_appender::rolling::daily(config.log_path()?, "kairotui.log");
     let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
     let subscriber = FmtSubscriber::builder()
-        .with_max_level(tracing::Level::TRACE)
+        .with_max_level(config.log_level())
         .with_writer(non_blocking)
         .finish();
 
     tracing::subscriber::set_global_default(subscriber)
         .expect("setting default subscriber failed");
 
-    let (mut state, mut data) = init()?;
+    if let Some(cmd) = args.command {
+        if let Some(e) = config_err {
+            eprintln!("warning: {e:#}, using defaults");
+        }
+        return cli::run(cmd, &config);
+    }
+
+    let (mut state, mut data) = init(config, config_err)?;
 
     let terminal = ratatui::init();
+    let _ = stdout().execute(EnableBracketedPaste);
     let result = run(terminal, &mut state, &mut data);
+    if let Err(e) = data.save().and_then(|_| data.compact()) {
+        warn!("failed to save on exit: {e:#}");
+    }
 
+    let _ = stdout().execute(DisableBracketedPaste);
     ratatui::restore();
     result?;
     Ok(())
 }
 
-fn render_popup(title: &str, msg: &Span, frame: &mut Frame) {
-    let c = msg.style.fg.unwrap_or(theme::TEXT);
-    let area = {
-        let vert = Layout::vertical([Constraint::Percentage(15)]).flex(Flex::Center);
-        let horz = Layout::horizontal([Constraint::Percentage(30)]).flex(Flex::Center);
-        let [area] = vert.areas(frame.area());
-        let [area] = horz.areas(area);
-        area
-    };
-    Paragraph::new(msg.clone())
-        .block(
-            Block::bordered()
-                .border_type(BorderType::Rounded)
-                .fg(c)
-                .title(title.to_span().into_centered_line()),
-        )
-        .centered()
-        .render(area, frame.buffer_mut());
-}
-
-fn render_input_dialog(title: &str, def: &str, frame: &mut Frame, state: &mut State) {
+fn render_input_dialog(
+    title: &str,
+    def: &str,
+    frame: &mut Frame,
+    state: &mut State,
+    data: &SaveData,
+) {
     let has_input = !state.input.is_empty();
     let txt = if !has_input {
         Line::from(def)
     } else {
-        let mut s = state.input_display.clone();
-        let l: &mut Span = s.spans.iter_mut().last().unwrap();
-        *l = Span::styled(format!("{}█", l.content.clone()), l.style);
-        s
+        state.input_display.clone()
     };
 
     let color = if has_input {
-        theme::TEXT
+        theme::text()
     } else {
-        theme::TEXT_ALT
+        theme::text_alt()
     };
 
     let area = {
@@ -136,117 +221,304 @@ fn render_input_dialog(title: &str, def: &str, frame: &mut Frame, state: &mut St
         area
     };
 
+    let block = Block::bordered()
+        .border_type(BorderType::Rounded)
+        .fg(theme::orange())
+        .title(title.to_span().into_centered_line());
+    let inner = block.inner(area);
+
+    // keep the cursor visible by scrolling long input horizontally
+    let cursor = state.input.cursor_width() as u16;
+    let scroll = cursor.saturating_sub(inner.width.saturating_sub(1));
+
     frame.render_widget(Clear, area);
-    Paragraph::new(txt.clone())
-        .block(
-            Block::bordered()
-                .border_type(BorderType::Rounded)
-                .fg(theme::ORANGE)
-                .title(title.to_span().into_centered_line()),
-        )
+    Paragraph::new(txt)
+        .block(block)
         .left_aligned()
         .fg(color)
+        .scroll((0, scroll))
         .render(area, frame.buffer_mut());
+    frame.set_cursor_position(Position::new(inner.x + cursor - scroll, inner.y));
+
+    if state.completion.is_active() {
+        complete::render(&mut state.completion, area, data, frame);
+    }
 }
 
-fn init() -> Result<(State, SaveData)> {
+fn init(config: Config, config_err: Option<anyhow::Error>) -> Result<(State, SaveData)> {
     let mut state = State {
-        list_state: ListState::default(),
-        input: String::from(""),
-        opened_once: false,
+        input: LineEditor::default(),
+        pending_keys: vec![],
+        completion: Completion::default(),
+        opened_once: !config.animations,
         input_dialog_active: false,
+        input_target: InputTarget::Tab,
         input_display: Line::default(),
-        main_panel_title: "",
         anims: RefCell::new(AnimationHandler {
             animations: HashMap::new(),
         }),
         focused_list: tab::ListType::Log,
         focused_list_idx: 0,
-        popup_active: false,
-        popup_msg: Span::raw(""),
+        modal: None,
+        help_active: false,
+        toast: None,
         input_default: ("", ""),
         rendered_lists: vec![],
         dt: 0.0,
+        config,
+        history: History::default(),
+        edit_notes: None,
+        form: None,
     };
 
     let _ = color_eyre::install();
 
-    state.main_panel_title = tab::ListType::Log.to_str();
-    let mut data_path = config_dir().unwrap();
-    data_path.push("kairotui");
-    fs::create_dir_all(&data_path)?;
-    data_path.push("save.dat");
+    if let Some(e) = config_err {
+        warn!("config error: {e:#}");
+        state.show_popup(Span::styled(format!("{e:#}"), theme::red()));
+    }
 
-    let mut data = SaveData::new(data_path.to_str().unwrap().to_string());
-    if fs::exists(&data_path)? {
-        data = data.load()?;
+    // a running daemon owns the data, so it is read and saved through it
+    let (storage, path) = (state.config.storage, state.config.data_path()?);
+    let (data, recovered) = match SaveData::connect(storage, &path)? {
+        Some(data) => (data, None),
+        None => SaveData::open_or_recover(storage, path)?,
+    };
+    if let Some(backup) = recovered {
+        let name = backup.file_name().unwrap_or_default().to_string_lossy();
+        state.show_popup(Span::styled(
+            format!("Save file was corrupt, restored from {name}"),
+            theme::yellow(),
+        ));
+    }
+    if let Err(e) = data.backup(state.config.backups) {
+        warn!("failed to create backup: {e:#}");
     }
 
-    let log_list = Box::new(LogList::new("Logs"));
-    state.rendered_lists.push(log_list);
+    state.rendered_lists = vec![
+        Box::new(LogList::new("Logs", LogType::Active, &state.config.keymap)),
+        Box::new(TagList::new("Tags")),
+        Box::new(LogList::new(
+            "Past Logs",
+            LogType::Past,
+            &state.config.keymap,
+        )),
+        Box::new(ReportTab::new("Reports")),
+    ];
     Ok((state, data))
 }
 
-fn delegate_enter(state: &mut State, data: &mut SaveData) {
-    match state.focused_list {
-        tab::ListType::Log => {
-            if let Some(i) = state.list_state.selected() {
-                let log = &mut data.logs[i];
-                log.done = true;
-                data.past_logs.push(log.clone());
-                data.logs.remove(i);
-            }
+/// Actions handled regardless of the focused tab.
+const GLOBAL_ACTIONS: [(Action, &str); 7] = [
+    (Action::Help, "help"),
+    (Action::Undo, "undo"),
+    (Action::Redo, "redo"),
+    (Action::NextTab, "next tab"),
+    (Action::PrevTab, "previous tab"),
+    (Action::Export, "export"),
+    (Action::Quit, "quit"),
+];
+
+fn is_global(action: Action) -> bool {
+    GLOBAL_ACTIONS.iter().any(|(a, _)| *a == action)
+}
+
+fn handle_key(key: KeyEvent, state: &mut State, data: &mut SaveData) -> bool {
+    if state.modal.is_none()
+        && let Some((form, _)) = &mut state.form
+    {
+        match form.handle_key(key) {
+            Some(FormResult::Submit) => submit_form(state, data),
+            Some(FormResult::Cancel) => state.form = None,
+            None => {}
         }
-        tab::ListType::Tag => {
-            state.input_dialog_active = true;
-            state.input_default.0 = " Edit Tag ";
-            state.input_default.1 =
-                "<name>: <hex> (e.g. #FF00FF) or <color name> (e.g. Green)";
+        return false;
+    }
+    if let Some(modal) = &state.modal {
+        let Some(result) = modal.handle_key(key) else {
+            return false;
+        };
+        let modal = state.modal.take();
+        return match (result, modal.map(|m| m.kind)) {
+            (ModalResult::Confirmed, Some(ModalKind::Confirm(action)))
+            | (ModalResult::Chose(action), _) => dispatch(action, true, state, data),
+            _ => false,
+        };
+    }
+    if state.help_active {
+        state.help_active = false;
+        return false;
+    }
+
+    match state.config.keymap.resolve(&mut state.pending_keys, key) {
+        Resolved::Action(action) => dispatch(action, false, state, data),
+        Resolved::Pending | Resolved::Unbound => false,
+    }
+}
+
+/// Applies the edit form, keeping it open with the error if the input is
+/// invalid and warning about logs the new times overlap with.
+fn submit_form(state: &mut State, data: &mut SaveData) {
+    let Some((form, (list, i))) = &mut state.form else {
+        return;
+    };
+    let past = *list == tab::ListType::PastLog;
+    let field = |label| form.value(label).unwrap_or_default();
+    let before = Snapshot::take(data);
+    let res = log::apply_edit(
+        data,
+        past,
+        *i,
+        field("Name"),
+        field("Tags"),
+        field("Start"),
+        form.value("End"),
+    );
+    let overlaps = match res {
+        Err(e) => {
+            form.error = Some(format!("{e:#}"));
+            return;
         }
-        _ => {}
+        overlaps => overlaps.unwrap_or_default(),
+    };
+    let name = field("Name").trim().to_string();
+    state.form = None;
+    state.history.push(format!("Edited '{name}'"), before, data);
+    if !overlaps.is_empty() {
+        let mut names: Vec<String> =
+            overlaps.iter().take(3).map(|n| format!("'{n}'")).collect();
+        if overlaps.len() > 3 {
+            names.push(format!("{} more", overlaps.len() - 3));
+        }
+        let msg = format!("'{name}' now overlaps with {}", names.join(", "));
+        state.show_popup(Span::styled(msg, theme::yellow()));
     }
 }
 
-fn handle_key(key: KeyEvent, state: &mut State, data: &mut SaveData) -> bool {
-    if state.popup_active {
-        state.popup_active = false;
+/// Runs `action`, first asking for confirmation if the config wants it and
+/// it hasn't been `confirmed` yet. Returns `true` to quit.
+fn dispatch(
+    action: Action,
+    confirmed: bool,
+    state: &mut State,
+    data: &mut SaveData,
+) -> bool {
+    let tab = &mut state.rendered_lists[state.focused_list_idx];
+    let global = is_global(action);
+    let available = global || tab.actions().iter().any(|(a, _)| *a == action);
+    // deleting a tag always asks how
+    let tag_delete =
+        action == Action::Delete && state.focused_list == tab::ListType::Tag;
+    // there's nothing to confirm for a tab action without a selection
+    let targeted = global || tab.selected().is_some();
+    if available
+        && targeted
+        && !confirmed
+        && !tag_delete
+        && state.config.confirms(action)
+    {
+        let prompt = tab.confirm_msg(action, data);
+        state.modal = Some(Modal::confirm(prompt, action));
         return false;
     }
 
-    match key.code {
-        event::KeyCode::Char(char) => match char {
-            'q' => return true,
-            'A' => {
-                if state.focused_list == tab::ListType::Log {
-                    state.input_dialog_active = true;
-                    state.input_default.0 = " New Log ";
-                    state.input_default.1 = "<log_name> (tag: <tag_name>)*";
-                }
+    match action {
+        Action::Quit => return true,
+        Action::Help => state.help_active = true,
+        Action::Export => {
+            state.input_dialog_active = true;
+            state.input_target = InputTarget::Export;
+            state.input_default.0 = " Export ";
+            state.input_default.1 =
+                "<csv|jsonl|md> <path> (from: <date>)? (to: <date>)? (tag: <name>)*";
+        }
+        Action::NextTab => ch_tab(state, true),
+        Action::PrevTab => ch_tab(state, false),
+        Action::Undo => {
+            let msg = state.history.undo(data);
+            state.show_toast(
+                msg.map_or("Nothing to undo".into(), |d| format!("Undid: {d}")),
+            );
+        }
+        Action::Redo => {
+            let msg = state.history.redo(data);
+            state.show_toast(
+                msg.map_or("Nothing to redo".into(), |d| format!("Redid: {d}")),
+            );
+        }
+        _ if !available => {}
+        Action::NewLog => {
+            state.input_dialog_active = true;
+            state.input_default.0 = " New Log ";
+            state.input_default.1 = "<log_name> (tag: <tag_name>)*";
+        }
+        Action::CustomRange => {
+            state.input_dialog_active = true;
+            state.input_default.0 = " Custom Range ";
+            state.input_default.1 = "<from> <to> (e.g. 2025-01-01 2025-01-31)";
+        }
+        Action::MergeTag => {
+            if tab.selected().is_some() {
+                state.input_dialog_active = true;
+                state.input_target = InputTarget::MergeTag;
+                state.input_default.0 = " Merge Into ";
+                state.input_default.1 = "<tag to merge into>";
             }
-            'D' => match state.focused_list {
-                tab::ListType::Log => {
-                    log::delete_selected(state);
-                }
-                tab::ListType::PastLog => {
-                    log::delete_past_log(state);
+        }
+        Action::Delete if tag_delete => {
+            if let Some(tag) = tab.selected().and_then(|i| data.tags.tags().get(i)) {
+                let prompt = format!("Delete tag '{}'?", tag.name());
+                state.modal = Some(Modal::choice(
+                    prompt,
+                    vec![
+                        ('s', "strip it from logs", Action::StripTag),
+                        ('d', "delete its logs too", Action::DeleteTagged),
+                    ],
+                ));
+            }
+        }
+        Action::EditNotes => {
+            state.edit_notes = tab.selected().map(|i| (state.focused_list, i));
+        }
+        Action::EditLog => {
+            let past = state.focused_list == tab::ListType::PastLog;
+            let logs = if past { &data.past_logs } else { &data.logs };
+            if let Some((i, log)) = tab.selected().and_then(|i| Some((i, logs.get(i)?)))
+            {
+                let time =
+                    |t: chrono::DateTime<chrono::Local>| t.format("%Y-%m-%d %H:%M");
+                let mut tags: Vec<&String> = log.tags.iter().collect();
+                tags.sort();
+                let tags = tags.iter().map(|t| t.as_str()).collect::<Vec<_>>();
+                let mut fields = vec![
+                    ("Name", log.name.clone()),
+                    ("Tags", tags.join(" ")),
+                    ("Start", time(log.start).to_string()),
+                ];
+                if past {
+                    fields.push(("End", time(log.end).to_string()));
                 }
-                _ => {}
-            },
-            'n' | 'j' => {
-                state.list_state.select_next();
+                state.form =
+                    Some((Form::new(" Edit Log ", fields), (state.focused_list, i)));
             }
-            'm' | 'k' => {
-                state.list_state.select_previous();
+        }
+        Action::Complete if state.focused_list == tab::ListType::Tag => {
+            if tab.selected().is_some() {
+                state.input_dialog_active = true;
+                state.input_default.0 = " Edit Tag ";
+                state.input_default.1 =
+                    "<name> (: <hex> (e.g. #FF00FF) or <color name> (e.g. Green))?";
             }
-            'J' => ch_tab(state, true),
-            'K' => ch_tab(state, false),
-            _ => {}
-        },
-        event::KeyCode::Enter => {
-            delegate_enter(state, data);
-        }
-        event::KeyCode::Tab => ch_tab(state, true),
-        _ => {}
+        }
+        _ if action.is_navigation() => {
+            tab.handle_action(action, data);
+        }
+        _ => {
+            let before = Snapshot::take(data);
+            if let Some(desc) = tab.handle_action(action, data) {
+                state.history.push(desc, before, data);
+            }
+        }
     }
     false
 }
@@ -258,52 +530,174 @@ fn ch_tab(state: &mut State, down: bool) {
     state.focused_list_idx = prev.checked_add_signed(ch as isize).unwrap_or(len - 1);
     state.focused_list_idx %= len;
     state.focused_list = tab::ListType::TYPES[state.focused_list_idx];
-    state.list_state.scroll_up_by(u16::MAX);
-    state.main_panel_title = state.focused_list.to_str();
 }
 
-fn handle_input(key: KeyEvent, state: &mut State) -> (Option<String>, bool) {
-    match key.code {
-        event::KeyCode::Enter => {
-            let ret = Some(state.input.clone());
-            state.input.clear();
-            state.update_input_display();
+/// What a key does in the input dialog, on top of the editing keys of
+/// [`LineEditor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InputKey {
+    Submit,
+    Cancel,
+    AcceptCompletion,
+    NextCompletion,
+    PrevCompletion,
+}
+
+impl InputKey {
+    fn describe(self) -> &'static str {
+        match self {
+            InputKey::Submit => "submit",
+            InputKey::Cancel => "cancel",
+            InputKey::AcceptCompletion => "accept completion",
+            InputKey::NextCompletion | InputKey::PrevCompletion => "select completion",
+        }
+    }
+}
+
+const INPUT_KEYS: [(event::KeyCode, InputKey); 6] = [
+    (event::KeyCode::Enter, InputKey::Submit),
+    (event::KeyCode::Esc, InputKey::Cancel),
+    (event::KeyCode::Tab, InputKey::AcceptCompletion),
+    (event::KeyCode::Down, InputKey::NextCompletion),
+    (event::KeyCode::Up, InputKey::PrevCompletion),
+    (event::KeyCode::BackTab, InputKey::PrevCompletion),
+];
+
+fn handle_input(
+    key: KeyEvent,
+    state: &mut State,
+    data: &SaveData,
+) -> (Option<String>, bool) {
+    let bound = INPUT_KEYS.iter().find(|(code, _)| *code == key.code);
+    let completing = state.completion.is_active();
+    match bound.map(|(_, k)| *k) {
+        Some(InputKey::Submit) => {
+            let ret = Some(state.input.take());
+            state.update_input_display(data);
             return (ret, true);
         }
-        event::KeyCode::Esc => return (None, true),
-        event::KeyCode::Backspace => {
-            state.input.pop();
+        Some(InputKey::Cancel) => {
+            state.input.clear();
+            state.update_input_display(data);
+            return (None, true);
         }
-        event::KeyCode::Char(c) => {
-            state.input.push(c);
+        Some(InputKey::NextCompletion) if completing => {
+            state.completion.select_next();
+        }
+        Some(InputKey::PrevCompletion) if completing => {
+            state.completion.select_previous();
+        }
+        Some(InputKey::AcceptCompletion) if completing => {
+            if let Some(tag) = state.completion.selected() {
+                let tag = format!("{tag} ");
+                let n = state.completion.partial_len;
+                state.input.replace_before_cursor(n, &tag);
+            }
+        }
+        _ => {
+            state.input.handle_key(key);
         }
-        _ => {}
     }
-    state.update_input_display();
+    state.update_input_display(data);
     (None, false)
 }
 
+fn handle_export(state: &mut State, data: &SaveData, input: &str) {
+    let res = export::parse_action(input).and_then(|(format, path, filter)| {
+        let n = export::write_file(&path, data, format, &filter)?;
+        Ok(format!("Exported {n} logs to {}", path.display()))
+    });
+    state.show_popup(res.map_or_else(
+        |e| Span::styled(format!("{e:#}"), theme::red()),
+        |msg| Span::styled(msg, theme::green()),
+    ));
+}
+
+/// Saves the data, merging in changes other processes made to the file
+/// while keeping every tab's selection on the same item.
+fn sync(state: &mut State, data: &mut SaveData) {
+    let ids: Vec<_> = state
+        .rendered_lists
+        .iter()
+        .map(|t| t.selection_id(data))
+        .collect();
+    match data.save() {
+        Err(e) => state.show_toast(format!("Not saved: {e:#}")),
+        merged if merged.as_ref().is_ok_and(|m| *m) => {
+            let mut found = vec![];
+            for (tab, id) in state.rendered_lists.iter_mut().zip(ids) {
+                found.push(id.is_none_or(|id| tab.reselect(&id, data)));
+            }
+            if !found[state.focused_list_idx] {
+                // whatever the form or modal was about is gone
+                state.form = None;
+                let stale = state.modal.as_ref().is_some_and(|m| match &m.kind {
+                    ModalKind::Message => false,
+                    ModalKind::Confirm(action) => !is_global(*action),
+                    ModalKind::Choice(_) => true,
+                });
+                if stale {
+                    state.modal = None;
+                }
+            } else if let Some((_, (_, i))) = &mut state.form {
+                // the form can only be open for the focused tab's selection
+                *i = state.rendered_lists[state.focused_list_idx]
+                    .selected()
+                    .unwrap_or(*i);
+            }
+            // the undo stack no longer matches the data
+            state.history = History::default();
+            state.show_toast("Loaded changes made outside kairotui".into());
+        }
+        _ => {}
+    }
+}
+
 fn handle_event(state: &mut State, data: &mut SaveData) -> bool {
-    let _ = data.save();
-    if let Event::Key(key) = event::read().unwrap() {
+    let ev = event::read().unwrap();
+    if let Event::Paste(text) = &ev {
+        if let Some((form, _)) = &mut state.form {
+            form.insert_str(text);
+        } else if state.input_dialog_active {
+            state.input.insert_str(text);
+            state.update_input_display(data);
+        }
+    }
+    if let Event::Key(key) = ev {
         if !state.input_dialog_active {
             return handle_key(key, state, data);
         }
-        let res = handle_input(key, state);
-        match state.focused_list {
-            tab::ListType::Log => {
-                if let Some(str) = res.0 {
-                    log::handle_add(state, str);
-                }
+        let (submitted, closed) = handle_input(key, state, data);
+        let target = state.input_target;
+        if closed {
+            state.input_dialog_active = false;
+            state.input_target = InputTarget::Tab;
+        }
+        let Some(str) = submitted else {
+            return false;
+        };
+        let before = Snapshot::take(data);
+        let change = match (target, state.focused_list) {
+            (InputTarget::Export, _) => {
+                handle_export(state, data, &str);
+                None
             }
-            tab::ListType::Tag => {
-                if let Some(str) = res.0 {
-                    tag::handle_edit(state, str);
+            (InputTarget::MergeTag, _) => tag::handle_merge(state, data, &str),
+            (InputTarget::Tab, tab::ListType::Log) => {
+                Some(LogList::handle_add(str, data, &state.config.default_tags))
+            }
+            (InputTarget::Tab, tab::ListType::Tag) => tag::handle_edit(state, data, str),
+            (InputTarget::Tab, _) => {
+                let tab = &mut state.rendered_lists[state.focused_list_idx];
+                if let Err(e) = tab.handle_input(str, data) {
+                    state.show_popup(Span::styled(e.to_string(), theme::red()));
                 }
+                None
             }
-            _ => {}
+        };
+        if let Some(desc) = change {
+            state.history.push(desc, before, data);
         }
-        state.input_dialog_active = !res.1;
     }
     false
 }
@@ -314,14 +708,25 @@ fn run(
     data: &mut SaveData,
 ) -> Result<()> {
     let mut last_frame = std::time::Instant::now();
+    let mut last_reload = Instant::now();
+    // a running daemon tells us as soon as it changed the data
+    let notes = daemon::Client::connect(&state.config.data_path()?)
+        .and_then(|client| client.watch().ok());
 
     loop {
         let now = Instant::now();
         state.dt = now.duration_since(last_frame).as_secs_f64();
         last_frame = now;
 
+        let notified = notes.as_ref().is_some_and(|rx| rx.try_iter().count() > 0);
+        if notified || last_reload.elapsed() >= RELOAD_INTERVAL {
+            last_reload = Instant::now();
+            if data.changed_on_disk() {
+                sync(state, data);
+            }
+        }
         log::update_logs(&mut data.logs);
-        terminal.draw(|x| render(x, state))?;
+        terminal.draw(|x| render(x, state, data))?;
 
         let timeout = if state.anims.borrow().running() {
             Duration::from_millis(32)
@@ -333,6 +738,10 @@ fn run(
             if handle_event(state, data) {
                 break;
             }
+            if let Some((list, i)) = state.edit_notes.take() {
+                terminal = edit_notes(terminal, state, data, list, i)?;
+            }
+            sync(state, data);
             std::thread::sleep(std::time::Duration::from_millis(1));
         }
     }
@@ -340,6 +749,103 @@ fn run(
     Ok(())
 }
 
+/// Creates a new directory only the current user can access, so files in
+/// it can't be read or swapped for symlinks by others.
+fn private_temp_dir() -> Result<PathBuf> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.mode(0o700);
+    let mut attempt = 0;
+    loop {
+        let name = format!("kairotui-{}-{attempt}", std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        match builder.create(&dir) {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 100 => {
+                attempt += 1;
+            }
+            res => return res.map(|_| dir).map_err(Into::into),
+        }
+    }
+}
+
+/// Opens the notes of log `i` in `$VISUAL`/`$EDITOR`, suspending the TUI
+/// while it runs. Returns the re-initialized terminal.
+fn edit_notes(
+    terminal: DefaultTerminal,
+    state: &mut State,
+    data: &mut SaveData,
+    list: tab::ListType,
+    i: usize,
+) -> Result<DefaultTerminal> {
+    let logs = match list {
+        tab::ListType::PastLog => &data.past_logs,
+        _ => &data.logs,
+    };
+    let Some(log) = logs.get(i) else {
+        return Ok(terminal);
+    };
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let dir = match private_temp_dir() {
+        Err(e) => {
+            let msg = format!("creating temp dir: {e:#}");
+            state.show_popup(Span::styled(msg, theme::red()));
+            return Ok(terminal);
+        }
+        dir => dir?,
+    };
+    let path = dir.join("notes.md");
+    let written = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)
+        .and_then(|mut f| f.write_all(log.notes.as_bytes()));
+    if let Err(e) = written {
+        let _ = std::fs::remove_dir_all(&dir);
+        let msg = format!("writing {}: {e}", path.display());
+        state.show_popup(Span::styled(msg, theme::red()));
+        return Ok(terminal);
+    }
+
+    drop(terminal);
+    let _ = stdout().execute(DisableBracketedPaste);
+    ratatui::restore();
+    let mut parts = editor.split_whitespace();
+    let status = std::process::Command::new(parts.next().unwrap_or("vi"))
+        .args(parts)
+        .arg(&path)
+        .status();
+    let mut terminal = ratatui::init();
+    let _ = stdout().execute(EnableBracketedPaste);
+    terminal.clear()?;
+
+    let notes = status.map_err(anyhow::Error::from).and_then(|s| {
+        anyhow::ensure!(s.success(), "{editor} exited with {s}");
+        Ok(std::fs::read_to_string(&path)?)
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    let notes = match notes {
+        Err(e) => {
+            state.show_popup(Span::styled(format!("{e:#}"), theme::red()));
+            return Ok(terminal);
+        }
+        notes => notes?.trim_end().to_string(),
+    };
+
+    let before = Snapshot::take(data);
+    let logs = match list {
+        tab::ListType::PastLog => &mut data.past_logs,
+        _ => &mut data.logs,
+    };
+    if logs[i].notes != notes {
+        logs[i].notes = notes;
+        let desc = format!("Edited notes of '{}'", logs[i].name);
+        state.history.push(desc, before, data);
+    }
+    Ok(terminal)
+}
+
 fn handle_main_layout_anims(areas: &[Rect; 2], state: &mut State) {
     add_anim_if_missing!(
         state,
@@ -357,62 +863,84 @@ fn handle_main_layout_anims(areas: &[Rect; 2], state: &mut State) {
     );
 }
 
-fn compute_main_layout(frame: &Frame, st: &mut State) -> (Rect, Rect) {
-    let [tabs_and_main] = Layout::vertical([Constraint::Fill(1)])
-        .margin(1)
-        .areas(frame.area());
+fn compute_main_layout(frame: &Frame, st: &mut State) -> (Rect, Rect, Rect) {
+    let [tabs_and_main, hint_area] =
+        Layout::vertical([Constraint::Fill(1), Constraint::Length(1)])
+            .margin(1)
+            .areas(frame.area());
     let [tab_area, main_area] =
         Layout::horizontal([Constraint::Length(20), Constraint::Min(10)])
             .areas(tabs_and_main);
     let [todo_area] = Layout::vertical([Constraint::Fill(1)]).areas(main_area);
 
-    handle_main_layout_anims(&[todo_area, tab_area], st);
-    (tab_area, todo_area)
+    if st.config.animations {
+        handle_main_layout_anims(&[todo_area, tab_area], st);
+    }
+    (tab_area, todo_area, hint_area)
 }
 
-fn render_main_screen(frame: &mut Frame, state: &mut State) {
-    let (tab_area, log_a) = compute_main_layout(frame, state);
-    let panel_txt = state.main_panel_title;
+fn render_main_screen(frame: &mut Frame, state: &mut State, data: &mut SaveData) {
+    let (tab_area, log_a, hint_area) = compute_main_layout(frame, state);
+    let panel_txt = state.rendered_lists[state.focused_list_idx]
+        .get_title()
+        .to_string();
 
     let outer = Block::bordered()
         .border_type(BorderType::Rounded)
-        .fg(theme::BLUE)
-        .bg(theme::BG0)
+        .fg(theme::blue())
+        .bg(theme::bg0())
         .title(panel_txt.to_span().into_centered_line());
 
-    match state.focused_list {
-        // tab::ListType::Log => {
-        //     log::render_log_list(state, &outer, &log_a, frame, log::LogType::Active);
-        // }
-        tab::ListType::PastLog => {
-            log::render_log_list(state, &outer, &log_a, frame, log::LogType::Past);
-        }
-        tab::ListType::Tag => render_tag_list(state, &outer, &log_a, frame),
-        _ => {}
-    }
-
-    // WARN:
-    state.rendered_lists[0].render(&outer, &log_a, frame);
+    state.rendered_lists[state.focused_list_idx].render(&outer, &log_a, frame, data);
 
     tab::render_tab_list(&tab_area, state, frame);
+    match (&state.toast, data.read_only()) {
+        (Some((msg, at)), _) if at.elapsed() < TOAST_DURATION => {
+            Paragraph::new(format!(" {msg}"))
+                .fg(theme::yellow())
+                .bg(theme::bg0())
+                .render(hint_area, frame.buffer_mut());
+        }
+        (_, Some(reason)) => {
+            Paragraph::new(format!(" Read-only, changes are not saved: {reason}"))
+                .fg(theme::red())
+                .bg(theme::bg0())
+                .render(hint_area, frame.buffer_mut());
+        }
+        _ => help::render_hint_bar(&hint_area, frame, state),
+    }
 }
 
-fn render(frame: &mut Frame, state: &mut State) {
+fn render(frame: &mut Frame, state: &mut State, data: &mut SaveData) {
     if state.opened_once
         || state.anims.borrow().animations.contains_key("intro_end")
             && state.anims.borrow().animations["intro_end"].effect.done()
     {
-        render_main_screen(frame, state);
+        render_main_screen(frame, state, data);
     } else {
         animation::render_intro(frame, state);
     }
 
     if state.input_dialog_active {
-        render_input_dialog(state.input_default.0, state.input_default.1, frame, state);
+        render_input_dialog(
+            state.input_default.0,
+            state.input_default.1,
+            frame,
+            state,
+            data,
+        );
+    }
+
+    if let Some((form, _)) = &state.form {
+        form.render(frame);
+    }
+
+    if state.help_active {
+        help::render(frame, state);
     }
 
-    if state.popup_active {
-        render_popup(" Popup ", &state.popup_msg, frame);
+    if let Some(modal) = &state.modal {
+        modal.render(frame);
     }
 
     state.anims.borrow_mut().progress(frame, state.dt, state);
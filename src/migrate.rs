@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde_json::{Map, Value, json};
+use std::collections::HashSet;
+use tracing::info;
+
+/// Version written by this build. Bump it and append a step to
+/// [`MIGRATIONS`] whenever the layout of `SaveData` changes.
+pub const CURRENT_VERSION: u64 = 5;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<()>;
+
+/// `MIGRATIONS[n]` upgrades a version `n` save to version `n + 1`.
+const MIGRATIONS: [Migration; CURRENT_VERSION as usize] =
+    [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4, v4_to_v5];
+
+/// Upgrades a raw save file to [`CURRENT_VERSION`] one step at a time.
+/// Files without a `version` field are treated as version 0.
+pub fn migrate(mut value: Value) -> Result<Value> {
+    let root = value
+        .as_object_mut()
+        .context("save file is not a JSON object")?;
+    let version = match root.get("version") {
+        Some(v) => v.as_u64().context("save file version is not a number")?,
+        None => 0,
+    };
+    if version > CURRENT_VERSION {
+        anyhow::bail!(
+            "save file version {version} is newer than supported ({CURRENT_VERSION})"
+        );
+    }
+
+    for (v, step) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        step(root).with_context(|| format!("migrating save file v{v} to v{}", v + 1))?;
+        info!("migrated save file from v{v} to v{}", v + 1);
+    }
+    root.insert("version".to_string(), json!(CURRENT_VERSION));
+    Ok(value)
+}
+
+fn logs_mut(root: &mut Map<String, Value>) -> impl Iterator<Item = &mut Value> {
+    root.iter_mut()
+        .filter(|(k, _)| *k == "logs" || *k == "past_logs")
+        .filter_map(|(_, v)| v.as_array_mut())
+        .flatten()
+}
+
+/// v0 stored `start`/`end` as millisecond epoch values from `serde_millis`;
+/// v1 stores RFC 3339 timestamps.
+fn v0_to_v1(root: &mut Map<String, Value>) -> Result<()> {
+    fn convert(v: &mut Value) -> Result<()> {
+        if let Some(ms) = v.as_i64() {
+            let date = DateTime::from_timestamp_millis(ms)
+                .context("timestamp out of range")?
+                .with_timezone(&Local);
+            *v = json!(date.to_rfc3339());
+        }
+        Ok(())
+    }
+
+    for log in logs_mut(root) {
+        for key in ["start", "end"] {
+            if let Some(v) = log.get_mut(key) {
+                convert(v)?;
+            }
+        }
+        if let Some(segments) = log.get_mut("segments").and_then(|s| s.as_array_mut()) {
+            for seg in segments {
+                for key in ["start", "end"] {
+                    if let Some(v) = seg.get_mut(key) {
+                        convert(v)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// v2 adds work segments and the paused flag; older logs become a single
+/// segment spanning their start and end.
+fn v1_to_v2(root: &mut Map<String, Value>) -> Result<()> {
+    for log in logs_mut(root) {
+        let log = log.as_object_mut().context("log is not an object")?;
+        if !log.contains_key("segments") {
+            let (start, end) = (log.get("start").cloned(), log.get("end").cloned());
+            let segment = json!({ "start": start, "end": end });
+            log.insert("segments".to_string(), json!([segment]));
+        }
+        log.entry("paused").or_insert(json!(false));
+    }
+    Ok(())
+}
+
+/// v3 adds free-form notes to logs.
+fn v2_to_v3(root: &mut Map<String, Value>) -> Result<()> {
+    for log in logs_mut(root) {
+        let log = log.as_object_mut().context("log is not an object")?;
+        log.entry("notes").or_insert(json!(""));
+    }
+    Ok(())
+}
+
+/// v4 keeps a journal of changes next to the data file and records how
+/// many of its events the file includes.
+fn v3_to_v4(root: &mut Map<String, Value>) -> Result<()> {
+    root.entry("journal_seq").or_insert(json!(0));
+    Ok(())
+}
+
+/// Id of a log saved before logs had ids, derived from its start so every
+/// copy of the log gets the same one.
+pub fn legacy_id(start_nanos: i64) -> String {
+    format!("legacy-{start_nanos}")
+}
+
+/// Gives every log in `lists` without an id its [`legacy_id`]. Logs that
+/// start at the same time are told apart by their order.
+pub fn add_log_ids<'a>(lists: impl IntoIterator<Item = &'a mut Value>) -> Result<()> {
+    let mut seen = HashSet::new();
+    for log in lists.into_iter().filter_map(|l| l.as_array_mut()).flatten() {
+        let log = log.as_object_mut().context("log is not an object")?;
+        if log.contains_key("id") {
+            continue;
+        }
+        let start = log
+            .get("start")
+            .and_then(|s| s.as_str())
+            .unwrap_or_default();
+        let nanos = DateTime::parse_from_rfc3339(start)
+            .ok()
+            .and_then(|t| t.timestamp_nanos_opt())
+            .with_context(|| format!("invalid log start '{start}'"))?;
+        let mut id = legacy_id(nanos);
+        for n in 2.. {
+            if seen.insert(id.clone()) {
+                break;
+            }
+            id = format!("{}-{n}", legacy_id(nanos));
+        }
+        log.insert("id".to_string(), json!(id));
+    }
+    Ok(())
+}
+
+/// v5 gives every log a stable id.
+fn v4_to_v5(root: &mut Map<String, Value>) -> Result<()> {
+    let mut logs = root.remove("logs").unwrap_or_else(|| json!([]));
+    let mut past_logs = root.remove("past_logs").unwrap_or_else(|| json!([]));
+    add_log_ids([&mut logs, &mut past_logs])?;
+    root.insert("logs".to_string(), logs);
+    root.insert("past_logs".to_string(), past_logs);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::SaveData;
+
+    const V0: &str = include_str!("../tests/fixtures/save_v0.json");
+    const V1: &str = include_str!("../tests/fixtures/save_v1.json");
+    const V2: &str = include_str!("../tests/fixtures/save_v2.json");
+    const V3: &str = include_str!("../tests/fixtures/save_v3.json");
+    const V4: &str = include_str!("../tests/fixtures/save_v4.json");
+
+    fn load(raw: &str) -> SaveData {
+        let value = migrate(serde_json::from_str(raw).unwrap()).unwrap();
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn v0_epoch_millis_become_timestamps() {
+        let data = load(V0);
+        let log = &data.past_logs[0];
+        assert_eq!(log.start.timestamp_millis(), 1_735_725_600_000);
+        assert_eq!(log.duration().num_minutes(), 90);
+        assert_eq!(log.segments.len(), 1);
+        assert!(!log.paused);
+        assert_eq!(data.logs.len(), 1);
+        assert_eq!(data.tags.tags()[0].name(), "backend");
+    }
+
+    #[test]
+    fn v1_gets_single_segment() {
+        let data = load(V1);
+        let log = &data.past_logs[0];
+        assert_eq!(log.segments.len(), 1);
+        assert_eq!(log.segments[0].start, log.start);
+        assert_eq!(log.segments[0].end, log.end);
+        assert_eq!(log.duration().num_minutes(), 45);
+    }
+
+    #[test]
+    fn v2_keeps_segments() {
+        let data = load(V2);
+        let log = &data.logs[0];
+        assert!(log.paused);
+        assert_eq!(log.segments.len(), 2);
+        assert_eq!(log.duration().num_minutes(), 30);
+    }
+
+    #[test]
+    fn v2_gets_empty_notes() {
+        let data = load(V2);
+        assert!(data.logs.iter().all(|l| l.notes.is_empty()));
+    }
+
+    #[test]
+    fn v3_keeps_notes() {
+        let data = load(V3);
+        let log = &data.past_logs[0];
+        assert_eq!(log.notes, "went through the parser changes");
+        assert_eq!(log.duration().num_minutes(), 60);
+        assert_eq!(data.journal_seq, 0);
+    }
+
+    #[test]
+    fn v3_includes_no_journal_events() {
+        for raw in [V0, V1, V2] {
+            assert_eq!(load(raw).journal_seq, 0);
+        }
+    }
+
+    #[test]
+    fn v4_logs_get_ids_from_their_start() {
+        let start = "2025-01-01T10:00:00+00:00";
+        let log = json!({ "start": start, "name": "a" });
+        let mut lists = [json!([log.clone(), log.clone()]), json!([log])];
+        add_log_ids(&mut lists).unwrap();
+        let nanos = 1_735_725_600_000_000_000;
+        assert_eq!(lists[0][0]["id"], json!(legacy_id(nanos)));
+        assert_eq!(lists[0][1]["id"], json!(format!("{}-2", legacy_id(nanos))));
+        assert_eq!(lists[1][0]["id"], json!(format!("{}-3", legacy_id(nanos))));
+
+        let data = load(V2);
+        let ids: HashSet<_> = data
+            .logs
+            .iter()
+            .chain(&data.past_logs)
+            .map(|l| &l.id)
+            .collect();
+        assert_eq!(ids.len(), data.logs.len() + data.past_logs.len());
+    }
+
+    #[test]
+    fn v4_keeps_journal_seq_and_gets_ids() {
+        let data = load(V4);
+        assert_eq!(data.journal_seq, 12);
+        let nanos = data.logs[0].start.timestamp_nanos_opt().unwrap();
+        assert_eq!(data.logs[0].id, legacy_id(nanos));
+        assert_eq!(data.past_logs[0].id, format!("{}-2", legacy_id(nanos)));
+    }
+
+    #[test]
+    fn v4_without_log_lists_gets_empty_ones() {
+        let value = migrate(json!({ "version": 4 })).unwrap();
+        assert_eq!(value["logs"], json!([]));
+        assert_eq!(value["past_logs"], json!([]));
+    }
+
+    #[test]
+    fn sets_current_version() {
+        for raw in [V0, V1, V2, V3, V4] {
+            let value = migrate(serde_json::from_str(raw).unwrap()).unwrap();
+            assert_eq!(value["version"], json!(CURRENT_VERSION));
+        }
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let value = json!({ "version": CURRENT_VERSION + 1 });
+        assert!(migrate(value).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use crate::{keymap::Action, theme};
+use ratatui::{
+    Frame,
+    crossterm::event::{KeyCode, KeyEvent},
+    layout::{Constraint, Flex, Layout},
+    style::Stylize,
+    text::{Line, Span, Text, ToSpan},
+    widgets::{Block, BorderType, Clear, Paragraph, Widget, Wrap},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModalResult {
+    Dismissed,
+    Confirmed,
+    Cancelled,
+    Chose(Action),
+}
+
+#[derive(Debug, Clone)]
+pub enum ModalKind {
+    /// Closed by any key.
+    Message,
+    /// Asks before running the action it holds.
+    Confirm(Action),
+    /// Offers one action per key.
+    Choice(Vec<(char, &'static str, Action)>),
+}
+
+/// A dialog drawn over everything else that takes all keys until closed.
+#[derive(Debug, Clone)]
+pub struct Modal {
+    pub title: &'static str,
+    pub msg: Span<'static>,
+    pub kind: ModalKind,
+}
+
+impl Modal {
+    pub fn message(msg: Span<'static>) -> Modal {
+        Modal {
+            title: " Popup ",
+            msg,
+            kind: ModalKind::Message,
+        }
+    }
+
+    pub fn confirm(prompt: String, action: Action) -> Modal {
+        Modal {
+            title: " Confirm ",
+            msg: Span::styled(prompt, theme::yellow()),
+            kind: ModalKind::Confirm(action),
+        }
+    }
+
+    pub fn choice(prompt: String, options: Vec<(char, &'static str, Action)>) -> Modal {
+        Modal {
+            title: " Choose ",
+            msg: Span::styled(prompt, theme::yellow()),
+            kind: ModalKind::Choice(options),
+        }
+    }
+
+    /// Returns the result once `key` closes the modal.
+    pub fn handle_key(&self, key: KeyEvent) -> Option<ModalResult> {
+        match &self.kind {
+            ModalKind::Message => Some(ModalResult::Dismissed),
+            ModalKind::Choice(options) => match key.code {
+                KeyCode::Char(c) if let Some(o) = options.iter().find(|o| o.0 == c) => {
+                    Some(ModalResult::Chose(o.2))
+                }
+                KeyCode::Char('n' | 'q') | KeyCode::Esc => Some(ModalResult::Cancelled),
+                _ => None,
+            },
+            ModalKind::Confirm(_) => match key.code {
+                KeyCode::Char('y' | 'Y') | KeyCode::Enter => {
+                    Some(ModalResult::Confirmed)
+                }
+                KeyCode::Char('n' | 'N' | 'q') | KeyCode::Esc => {
+                    Some(ModalResult::Cancelled)
+                }
+                _ => None,
+            },
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let c = self.msg.style.fg.unwrap_or(theme::text());
+        let mut text = Text::from(Line::from(self.msg.clone()));
+        match &self.kind {
+            ModalKind::Message => {}
+            ModalKind::Confirm(_) => {
+                text.push_line(Line::default());
+                text.push_line(Line::from(vec![
+                    Span::styled("y", theme::orange()).bold(),
+                    Span::styled("es / ", theme::text_alt()),
+                    Span::styled("n", theme::orange()).bold(),
+                    Span::styled("o", theme::text_alt()),
+                ]));
+            }
+            ModalKind::Choice(options) => {
+                text.push_line(Line::default());
+                let cancel = [('n', "cancel")];
+                let options = options.iter().map(|o| (o.0, o.1)).chain(cancel);
+                for (key, desc) in options {
+                    text.push_line(Line::from(vec![
+                        Span::styled(key.to_string(), theme::orange()).bold(),
+                        Span::styled(format!(" {desc}"), theme::text_alt()),
+                    ]));
+                }
+            }
+        }
+
+        let height = (frame.area().height * 15 / 100).max(text.height() as u16 + 2);
+        let area = {
+            let vert = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center);
+            let horz =
+                Layout::horizontal([Constraint::Percentage(30)]).flex(Flex::Center);
+            let [area] = vert.areas(frame.area());
+            let [area] = horz.areas(area);
+            area
+        };
+
+        frame.render_widget(Clear, area);
+        Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .fg(c)
+                    .bg(theme::bg0())
+                    .title(self.title.to_span().into_centered_line()),
+            )
+            .centered()
+            .wrap(Wrap { trim: true })
+            .render(area, frame.buffer_mut());
+    }
+}
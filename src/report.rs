@@ -0,0 +1,463 @@
+use crate::{
+    data::SaveData,
+    keymap::Action,
+    log::{Log, duration_as_hhmmss},
+    tab::Tab,
+    theme,
+};
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Days, Local, NaiveDate, TimeDelta, TimeZone};
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{
+        Bar, BarChart, BarGroup, Block, BorderType, Paragraph, Sparkline, Widget,
+    },
+};
+use std::collections::BTreeMap;
+
+pub const UNTAGGED: &str = "(untagged)";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Range {
+    Today,
+    Week,
+    Month,
+    Custom(NaiveDate, NaiveDate),
+}
+
+impl Range {
+    const PRESETS: [Range; 3] = [Range::Today, Range::Week, Range::Month];
+
+    /// Start (inclusive) and end (exclusive) of the range, in local time.
+    pub fn bounds(self) -> (DateTime<Local>, DateTime<Local>) {
+        let today = Local::now().date_naive();
+        let (from, to) = match self {
+            Range::Today => (today, today),
+            Range::Week => (
+                today - Days::new(today.weekday().num_days_from_monday() as u64),
+                today,
+            ),
+            Range::Month => (today.with_day(1).unwrap_or(today), today),
+            Range::Custom(from, to) => (from, to),
+        };
+        (
+            start_of_day(from),
+            start_of_day(to.checked_add_days(Days::new(1)).unwrap_or(to)),
+        )
+    }
+
+    fn label(self) -> String {
+        match self {
+            Range::Today => "Today".to_string(),
+            Range::Week => "This week".to_string(),
+            Range::Month => "This month".to_string(),
+            Range::Custom(from, to) => format!("{from} – {to}"),
+        }
+    }
+}
+
+pub fn start_of_day(date: NaiveDate) -> DateTime<Local> {
+    start_of_day_in(date, &Local)
+}
+
+/// First moment of `date` in `tz`. When midnight falls in a DST gap the day
+/// starts at the first whole hour that exists.
+fn start_of_day_in<Tz: TimeZone>(date: NaiveDate, tz: &Tz) -> DateTime<Tz> {
+    (0..24)
+        .find_map(|hour| {
+            let local = tz.from_local_datetime(&date.and_hms_opt(hour, 0, 0)?);
+            local.clone().earliest().or(local.latest())
+        })
+        .unwrap_or_else(|| tz.from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()))
+}
+
+/// Parses `<from> <to>` (`YYYY-MM-DD`) into a custom range.
+pub fn parse_range(input: &str) -> Result<Range> {
+    let mut parts = input.split_whitespace();
+    let (Some(from), to) = (parts.next(), parts.next()) else {
+        anyhow::bail!("expected <from> <to>");
+    };
+    let from = NaiveDate::parse_from_str(from, "%Y-%m-%d")?;
+    let to = match to {
+        Some(to) => NaiveDate::parse_from_str(to, "%Y-%m-%d")?,
+        None => from,
+    };
+    if to < from {
+        anyhow::bail!("end date is before start date");
+    }
+    Ok(Range::Custom(from, to))
+}
+
+#[derive(Debug, Default)]
+pub struct Totals {
+    pub by_tag: BTreeMap<String, TimeDelta>,
+    pub by_day: BTreeMap<NaiveDate, TimeDelta>,
+    pub total: TimeDelta,
+}
+
+/// Sums the time spent in `[from, to)` over active and past logs, clipping
+/// every work segment to the range and splitting it at midnight.
+pub fn compute_totals(
+    data: &SaveData,
+    from: DateTime<Local>,
+    to: DateTime<Local>,
+) -> Totals {
+    let mut totals = Totals::default();
+    let mut day = from.date_naive();
+    while start_of_day(day) < to {
+        totals.by_day.insert(day, TimeDelta::zero());
+        day = day.succ_opt().unwrap();
+    }
+
+    for log in data.logs.iter().chain(data.past_logs.iter()) {
+        for (start, end) in log_spans(log) {
+            let (start, end) = (start.max(from), end.min(to));
+            if end <= start {
+                continue;
+            }
+            let dur = end - start;
+            totals.total += dur;
+
+            if log.tags.is_empty() {
+                *totals.by_tag.entry(UNTAGGED.to_string()).or_default() += dur;
+            }
+            for t in &log.tags {
+                *totals.by_tag.entry(t.clone()).or_default() += dur;
+            }
+
+            let mut cur = start;
+            while cur < end {
+                let next_day = start_of_day(cur.date_naive().succ_opt().unwrap());
+                let chunk_end = next_day.min(end);
+                *totals.by_day.entry(cur.date_naive()).or_default() += chunk_end - cur;
+                cur = chunk_end;
+            }
+        }
+    }
+    totals
+}
+
+fn log_spans(log: &Log) -> Vec<(DateTime<Local>, DateTime<Local>)> {
+    if log.segments.is_empty() {
+        return vec![(log.start, log.end)];
+    }
+    log.segments.iter().map(|s| (s.start, s.end)).collect()
+}
+
+pub struct ReportTab {
+    title: String,
+    range: Range,
+}
+
+impl ReportTab {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: format!("| {} |", title),
+            range: Range::Today,
+        }
+    }
+
+    fn cycle_range(&mut self, forward: bool) {
+        let len = Range::PRESETS.len();
+        let idx = Range::PRESETS
+            .iter()
+            .position(|r| *r == self.range)
+            .map(|i| {
+                if forward {
+                    (i + 1) % len
+                } else {
+                    (i + len - 1) % len
+                }
+            })
+            .unwrap_or(0);
+        self.range = Range::PRESETS[idx];
+    }
+}
+
+impl Tab for ReportTab {
+    fn render(
+        &mut self,
+        blk: &Block,
+        area: &Rect,
+        frame: &mut Frame,
+        data: &mut SaveData,
+    ) {
+        let (from, to) = self.range.bounds();
+        let totals = compute_totals(data, from, to);
+
+        let inner = blk.inner(*area);
+        blk.render(*area, frame.buffer_mut());
+
+        let [header, tag_area, day_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Fill(1),
+            Constraint::Length(8),
+        ])
+        .margin(1)
+        .areas(inner);
+
+        Paragraph::new(vec![
+            Line::from(vec![
+                Span::styled(self.range.label(), theme::orange()).bold(),
+                Span::styled(
+                    format!("  total {}", duration_as_hhmmss(totals.total)),
+                    theme::blue(),
+                ),
+            ]),
+            Line::styled(
+                "<h/l> range  <c> custom range",
+                Style::default().fg(theme::text_alt()),
+            ),
+        ])
+        .render(header, frame.buffer_mut());
+
+        let mut tags: Vec<(&String, &TimeDelta)> = totals.by_tag.iter().collect();
+        tags.sort_by(|a, b| b.1.cmp(a.1));
+        let bars: Vec<Bar> = tags
+            .iter()
+            .map(|(name, dur)| {
+                let color = data
+                    .tags
+                    .tags()
+                    .iter()
+                    .find(|t| t.name() == name.as_str())
+                    .map(|t| Color::from_u32(*t.color()))
+                    .unwrap_or(theme::text_alt());
+                Bar::default()
+                    .value(dur.num_seconds().max(0) as u64)
+                    .text_value(duration_as_hhmmss(**dur))
+                    .label(Line::from(name.to_string()))
+                    .style(Style::default().fg(color))
+                    .value_style(Style::default().fg(theme::bg0()).bg(color))
+            })
+            .collect();
+
+        BarChart::default()
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .fg(theme::aqua())
+                    .title(" Per tag "),
+            )
+            .direction(Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .data(BarGroup::default().bars(&bars))
+            .render(tag_area, frame.buffer_mut());
+
+        let days: Vec<u64> = totals
+            .by_day
+            .values()
+            .map(|d| d.num_seconds().max(0) as u64)
+            .collect();
+        let title = match (totals.by_day.keys().next(), totals.by_day.keys().last()) {
+            (Some(first), Some(last)) if first != last => {
+                format!(" Per day ({first} – {last}) ")
+            }
+            (Some(day), _) => format!(" Per day ({day}) "),
+            _ => " Per day ".to_string(),
+        };
+        Sparkline::default()
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .fg(theme::purple())
+                    .title(title),
+            )
+            .data(&days)
+            .style(Style::default().fg(theme::yellow()))
+            .render(day_area, frame.buffer_mut());
+    }
+
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_line(&self) -> Line<'static> {
+        let icon = theme::unicode_icon(0xf080, theme::purple());
+        let name = Span::raw("Reports");
+        Line::from(vec![icon, name])
+    }
+
+    fn actions(&self) -> &'static [(Action, &'static str)] {
+        &[
+            (Action::NextRange, "next range"),
+            (Action::PrevRange, "previous range"),
+            (Action::CustomRange, "custom range"),
+        ]
+    }
+
+    fn handle_action(&mut self, action: Action, _data: &mut SaveData) -> Option<String> {
+        match action {
+            Action::NextRange => self.cycle_range(true),
+            Action::PrevRange => self.cycle_range(false),
+            _ => {}
+        }
+        None
+    }
+
+    fn selected(&self) -> Option<usize> {
+        None
+    }
+
+    fn handle_input(&mut self, input: String, _data: &mut SaveData) -> Result<()> {
+        self.range = parse_range(&input)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, MappedLocalTime, NaiveDateTime, Timelike};
+
+    fn at(day: u32, hour: u32, min: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 1, day, hour, min, 0).unwrap()
+    }
+
+    fn log(start: DateTime<Local>, end: DateTime<Local>, tags: &[&str]) -> Log {
+        let mut log =
+            Log::new("work".into(), tags.iter().map(|t| t.to_string()).collect());
+        log.set_span(start, end);
+        log.done = true;
+        log
+    }
+
+    fn data(logs: Vec<Log>) -> SaveData {
+        let mut data = SaveData::new();
+        data.past_logs = logs;
+        data
+    }
+
+    #[test]
+    fn clips_segments_to_the_range() {
+        let data = data(vec![log(at(6, 9, 0), at(6, 11, 0), &["a"])]);
+        let totals = compute_totals(&data, at(6, 10, 0), at(7, 0, 0));
+        assert_eq!(totals.total, TimeDelta::hours(1));
+        assert_eq!(totals.by_tag["a"], TimeDelta::hours(1));
+    }
+
+    #[test]
+    fn range_end_is_exclusive() {
+        let data = data(vec![
+            log(at(6, 9, 0), at(6, 10, 0), &[]),
+            log(at(7, 0, 0), at(7, 1, 0), &[]),
+        ]);
+        let totals = compute_totals(&data, at(6, 0, 0), at(7, 0, 0));
+        assert_eq!(totals.total, TimeDelta::hours(1));
+        assert_eq!(totals.by_day.len(), 1);
+    }
+
+    #[test]
+    fn splits_at_midnight() {
+        let data = data(vec![log(at(6, 23, 0), at(7, 1, 30), &[])]);
+        let totals = compute_totals(&data, at(6, 0, 0), at(8, 0, 0));
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        assert_eq!(totals.by_day[&day(6)], TimeDelta::hours(1));
+        assert_eq!(totals.by_day[&day(7)], TimeDelta::minutes(90));
+        assert_eq!(totals.total, TimeDelta::minutes(150));
+    }
+
+    #[test]
+    fn every_day_of_the_range_is_listed() {
+        let totals = compute_totals(&data(vec![]), at(6, 0, 0), at(9, 0, 0));
+        assert_eq!(totals.by_day.len(), 3);
+        assert!(totals.by_day.values().all(|d| d.is_zero()));
+    }
+
+    #[test]
+    fn counts_time_towards_every_tag() {
+        let data = data(vec![
+            log(at(6, 9, 0), at(6, 10, 0), &["a", "b"]),
+            log(at(6, 11, 0), at(6, 11, 30), &[]),
+        ]);
+        let totals = compute_totals(&data, at(6, 0, 0), at(7, 0, 0));
+        assert_eq!(totals.by_tag["a"], TimeDelta::hours(1));
+        assert_eq!(totals.by_tag["b"], TimeDelta::hours(1));
+        assert_eq!(totals.by_tag[UNTAGGED], TimeDelta::minutes(30));
+        assert_eq!(totals.total, TimeDelta::minutes(90));
+    }
+
+    #[test]
+    fn custom_range_includes_its_last_day() {
+        let day = NaiveDate::from_ymd_opt(2025, 1, 6).unwrap();
+        let (from, to) = Range::Custom(day, day).bounds();
+        assert_eq!((from, to), (at(6, 0, 0), at(7, 0, 0)));
+    }
+
+    /// Zone that moves its clocks from midnight to 01:00 on 2025-03-30.
+    #[derive(Clone, Copy, Debug)]
+    struct GapAtMidnight;
+
+    impl GapAtMidnight {
+        fn gap_day() -> NaiveDate {
+            NaiveDate::from_ymd_opt(2025, 3, 30).unwrap()
+        }
+
+        fn offset(after: bool) -> FixedOffset {
+            FixedOffset::east_opt(if after { 3600 } else { 0 }).unwrap()
+        }
+    }
+
+    impl TimeZone for GapAtMidnight {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            GapAtMidnight
+        }
+
+        fn offset_from_local_date(
+            &self,
+            local: &NaiveDate,
+        ) -> MappedLocalTime<FixedOffset> {
+            MappedLocalTime::Single(Self::offset(*local > Self::gap_day()))
+        }
+
+        fn offset_from_local_datetime(
+            &self,
+            local: &NaiveDateTime,
+        ) -> MappedLocalTime<FixedOffset> {
+            match local.date().cmp(&Self::gap_day()) {
+                std::cmp::Ordering::Equal if local.hour() < 1 => MappedLocalTime::None,
+                order => MappedLocalTime::Single(Self::offset(order.is_ge())),
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            Self::offset(*utc >= Self::gap_day())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset(utc.date() >= Self::gap_day())
+        }
+    }
+
+    #[test]
+    fn day_starting_in_a_dst_gap_starts_at_the_first_valid_hour() {
+        let day = GapAtMidnight::gap_day();
+        let start = start_of_day_in(day, &GapAtMidnight);
+        assert_eq!(start.naive_local(), day.and_hms_opt(1, 0, 0).unwrap());
+        let before = start_of_day_in(day.pred_opt().unwrap(), &GapAtMidnight);
+        let after = start_of_day_in(day.succ_opt().unwrap(), &GapAtMidnight);
+        assert_eq!(start - before, TimeDelta::hours(24));
+        assert_eq!(after - start, TimeDelta::hours(23));
+    }
+
+    #[test]
+    fn parses_custom_ranges() {
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        assert_eq!(
+            parse_range("2025-01-06 2025-01-08").unwrap(),
+            Range::Custom(day(6), day(8))
+        );
+        assert_eq!(
+            parse_range("2025-01-06").unwrap(),
+            Range::Custom(day(6), day(6))
+        );
+        assert!(parse_range("2025-01-08 2025-01-06").is_err());
+        assert!(parse_range("").is_err());
+    }
+}
@@ -0,0 +1,632 @@
+use crate::{
+    data::SaveData,
+    log::Log,
+    migrate::{self, CURRENT_VERSION},
+    tag::Tag,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use rusqlite::{Connection, ErrorCode, OptionalExtension, Transaction, params};
+use serde::Deserialize;
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::{self, Debug, Display},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+/// Which [`Storage`] keeps the data file, set by `storage` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    #[default]
+    Json,
+    Sqlite,
+}
+
+impl Backend {
+    /// File name used when `data_file` isn't set.
+    pub fn default_file(self) -> &'static str {
+        match self {
+            Backend::Json => "save.dat",
+            Backend::Sqlite => "save.db",
+        }
+    }
+
+    pub fn open(self, path: &Path) -> Result<Box<dyn Storage>> {
+        Ok(match self {
+            Backend::Json => Box::new(JsonStorage {
+                path: path.to_path_buf(),
+            }),
+            Backend::Sqlite => Box::new(SqliteStorage::open(path)?),
+        })
+    }
+}
+
+/// Error of a [`Storage`] whose file exists but can't be parsed, as opposed
+/// to one that can't be read at all.
+#[derive(Debug)]
+pub struct Corrupt(anyhow::Error);
+
+impl Display for Corrupt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#}", self.0)
+    }
+}
+
+impl std::error::Error for Corrupt {}
+
+fn corrupt(e: impl Into<anyhow::Error>) -> anyhow::Error {
+    Corrupt(e.into()).into()
+}
+
+/// Whether `e` was caused by a [`Corrupt`] file.
+pub fn is_corrupt(e: &anyhow::Error) -> bool {
+    e.chain().any(|c| c.is::<Corrupt>())
+}
+
+/// Where [`SaveData`] is read from and written to.
+pub trait Storage: Debug {
+    /// Reads the stored data, or `None` if nothing was saved yet.
+    fn load(&self) -> Result<Option<SaveData>>;
+    fn save(&self, data: &SaveData) -> Result<()>;
+}
+
+/// The whole of [`SaveData`] as one JSON document.
+#[derive(Debug)]
+pub struct JsonStorage {
+    path: PathBuf,
+}
+
+impl Storage for JsonStorage {
+    fn load(&self) -> Result<Option<SaveData>> {
+        if !fs::exists(&self.path)? {
+            return Ok(None);
+        }
+        let str = fs::read_to_string(&self.path)?;
+        let raw: serde_json::Value = serde_json::from_str(&str).map_err(corrupt)?;
+        let raw = migrate::migrate(raw)?;
+        Ok(Some(serde_json::from_value(raw).map_err(corrupt)?))
+    }
+
+    /// Writes to a temporary file next to the save file and renames it over
+    /// the original, so a crash mid-write never leaves a truncated file.
+    fn save(&self, data: &SaveData) -> Result<()> {
+        let json = serde_json::to_string(data)?;
+        let tmp = PathBuf::from(format!("{}.tmp", self.path.display()));
+        {
+            let mut file = File::create(&tmp)?;
+            file.write_all(json.as_bytes())?;
+            file.sync_all()?;
+        }
+        fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS meta (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS logs (
+    id INTEGER PRIMARY KEY,
+    uid TEXT NOT NULL UNIQUE,
+    past INTEGER NOT NULL,
+    rank REAL NOT NULL,
+    name TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    done INTEGER NOT NULL,
+    paused INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    segments TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS logs_order ON logs (past, rank);
+CREATE INDEX IF NOT EXISTS logs_start ON logs (start_time);
+CREATE TABLE IF NOT EXISTS tags (
+    position INTEGER PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    color INTEGER NOT NULL,
+    refs INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS log_tags (
+    log_id INTEGER NOT NULL REFERENCES logs (id) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    PRIMARY KEY (log_id, tag)
+);
+CREATE INDEX IF NOT EXISTS log_tags_tag ON log_tags (tag);
+";
+
+/// Rebuilds a `logs` table from before logs had ids, which kept them in
+/// `(past, position)` order. Ids are filled in like
+/// [`migrate::add_log_ids`] does. Foreign keys are off so `log_tags` rows
+/// survive the old table being dropped.
+const ADD_LOG_IDS: &str = "
+PRAGMA foreign_keys = OFF;
+BEGIN;
+CREATE TABLE logs_v5 (
+    id INTEGER PRIMARY KEY,
+    uid TEXT NOT NULL UNIQUE,
+    past INTEGER NOT NULL,
+    rank REAL NOT NULL,
+    name TEXT NOT NULL,
+    start_time INTEGER NOT NULL,
+    end_time INTEGER NOT NULL,
+    done INTEGER NOT NULL,
+    paused INTEGER NOT NULL,
+    notes TEXT NOT NULL,
+    segments TEXT NOT NULL
+);
+INSERT INTO logs_v5 (id, uid, past, rank, name, start_time, end_time, done, paused,
+                     notes, segments)
+SELECT id,
+       'legacy-' || start_time || CASE WHEN n > 1 THEN '-' || n ELSE '' END,
+       past, position, name, start_time, end_time, done, paused, notes, segments
+FROM (SELECT *, ROW_NUMBER() OVER (PARTITION BY start_time ORDER BY past, position) AS n
+      FROM logs);
+DROP TABLE logs;
+ALTER TABLE logs_v5 RENAME TO logs;
+COMMIT;
+PRAGMA foreign_keys = ON;
+";
+
+/// Rows as they were last written, so a save only touches what changed.
+#[derive(Debug, Default)]
+struct Written {
+    meta: Option<(u64, bool, u64)>,
+    /// `(past, rank, log)` of every log row by id, or `None` if the rows
+    /// in the database aren't known.
+    logs: Option<HashMap<String, (bool, f64, Log)>>,
+    tags: Option<Vec<(String, u32, i32)>>,
+}
+
+/// An SQLite database with one row per log, tag and log tag. Times are
+/// stored as nanoseconds since the epoch.
+#[derive(Debug)]
+pub struct SqliteStorage {
+    conn: Connection,
+    written: RefCell<Written>,
+}
+
+fn nanos(t: DateTime<Local>) -> Result<i64> {
+    t.timestamp_nanos_opt().context("time out of range")
+}
+
+fn from_nanos(ns: i64) -> DateTime<Local> {
+    DateTime::from_timestamp_nanos(ns).with_timezone(&Local)
+}
+
+/// Sort keys for a list whose items had the `old` keys, if any. Keeps the
+/// longest increasing run of old keys and fits the rest in between, so only
+/// moved and new items need their row written.
+fn ranks(old: &[Option<f64>]) -> Vec<f64> {
+    // longest increasing subsequence: `tails[k]` ends the best one of
+    // length `k + 1` found so far, `prev` links each item to the one before
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; old.len()];
+    for (i, rank) in old.iter().enumerate() {
+        let Some(rank) = *rank else { continue };
+        let k = tails.partition_point(|&t| old[t].is_some_and(|r| r < rank));
+        prev[i] = k.checked_sub(1).map(|k| tails[k]);
+        if k == tails.len() {
+            tails.push(i);
+        } else {
+            tails[k] = i;
+        }
+    }
+    let mut kept = vec![None; old.len()];
+    let mut next = tails.last().copied();
+    while let Some(i) = next {
+        kept[i] = old[i];
+        next = prev[i];
+    }
+
+    let mut ranks = vec![0.0; old.len()];
+    let mut lo = None;
+    let mut gap = Vec::new();
+    for i in 0..=old.len() {
+        let rank = kept.get(i).copied().flatten();
+        if rank.is_none() && i < old.len() {
+            gap.push(i);
+            continue;
+        }
+        let n = gap.len() as f64;
+        for (k, &j) in gap.iter().enumerate() {
+            let k = k as f64 + 1.0;
+            ranks[j] = match (lo, rank) {
+                (None, None) => k,
+                (Some(lo), None) => lo + k,
+                (None, Some(hi)) => hi - (n + 1.0 - k),
+                (Some(lo), Some(hi)) => lo + (hi - lo) * k / (n + 1.0),
+            };
+        }
+        gap.clear();
+        if let Some(rank) = rank {
+            ranks[i] = rank;
+            lo = Some(rank);
+        }
+    }
+    if ranks.windows(2).any(|w| w[0] >= w[1]) {
+        // out of precision between two kept keys
+        return (1..=old.len()).map(|k| k as f64).collect();
+    }
+    ranks
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> Result<SqliteStorage> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open {}", path.display()))?;
+        let not_kairotui = |e: rusqlite::Error| {
+            let broken = matches!(
+                e.sqlite_error_code(),
+                Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt)
+            );
+            let e = anyhow::Error::from(e)
+                .context(format!("{} is not a kairotui database", path.display()));
+            if broken { corrupt(e) } else { e }
+        };
+        let positional: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info('logs') WHERE name = 'position'",
+                [],
+                |r| r.get(0),
+            )
+            .map_err(not_kairotui)?;
+        if positional {
+            conn.execute_batch(ADD_LOG_IDS)
+                .context("failed to add ids to logs in the database")?;
+        }
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA).map_err(not_kairotui)?;
+        Ok(SqliteStorage {
+            conn,
+            written: RefCell::default(),
+        })
+    }
+
+    fn write(
+        &self,
+        tx: &Transaction,
+        data: &SaveData,
+        written: &mut Written,
+    ) -> Result<()> {
+        let meta = (data.version, data.opened_once, data.journal_seq);
+        if written.meta != Some(meta) {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO meta (key, value) VALUES (?1, ?2)
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            )?;
+            stmt.execute(params!["version", meta.0])?;
+            stmt.execute(params!["opened_once", meta.1])?;
+            stmt.execute(params!["journal_seq", meta.2])?;
+            written.meta = Some(meta);
+        }
+
+        let rows = match &mut written.logs {
+            Some(rows) => rows,
+            None => {
+                // start over rather than guess which rows are stale
+                tx.execute("DELETE FROM logs", [])?;
+                written.logs.insert(HashMap::new())
+            }
+        };
+        for (past, logs) in [(false, &data.logs), (true, &data.past_logs)] {
+            let old: Vec<_> = logs
+                .iter()
+                .map(|l| rows.get(&l.id).filter(|r| r.0 == past).map(|r| r.1))
+                .collect();
+            for (log, rank) in logs.iter().zip(ranks(&old)) {
+                let unchanged = rows
+                    .get(&log.id)
+                    .is_some_and(|(p, r, l)| *p == past && *r == rank && l == log);
+                if !unchanged {
+                    write_log(tx, past, rank, log)?;
+                    rows.insert(log.id.clone(), (past, rank, log.clone()));
+                }
+            }
+        }
+        let ids: HashSet<&str> = data
+            .logs
+            .iter()
+            .chain(&data.past_logs)
+            .map(|l| l.id.as_str())
+            .collect();
+        let gone: Vec<String> = rows
+            .keys()
+            .filter(|id| !ids.contains(id.as_str()))
+            .cloned()
+            .collect();
+        for id in gone {
+            tx.execute("DELETE FROM logs WHERE uid = ?1", [&id])?;
+            rows.remove(&id);
+        }
+
+        let tags: Vec<_> = data
+            .tags
+            .tags()
+            .iter()
+            .map(|t| (t.name().to_string(), *t.color(), t.refs))
+            .collect();
+        if written.tags.as_ref() != Some(&tags) {
+            tx.execute("DELETE FROM tags", [])?;
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO tags (position, name, color, refs) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for (i, (name, color, refs)) in tags.iter().enumerate() {
+                stmt.execute(params![i, name, color, refs])?;
+            }
+            written.tags = Some(tags);
+        }
+        Ok(())
+    }
+}
+
+fn write_log(tx: &Transaction, past: bool, rank: f64, log: &Log) -> Result<()> {
+    let id: i64 = tx
+        .prepare_cached(
+            "INSERT INTO logs (uid, past, rank, name, start_time, end_time, done, paused,
+                               notes, segments)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (uid) DO UPDATE SET
+                 past = excluded.past,
+                 rank = excluded.rank,
+                 name = excluded.name,
+                 start_time = excluded.start_time,
+                 end_time = excluded.end_time,
+                 done = excluded.done,
+                 paused = excluded.paused,
+                 notes = excluded.notes,
+                 segments = excluded.segments
+             RETURNING id",
+        )?
+        .query_row(
+            params![
+                log.id,
+                past,
+                rank,
+                log.name,
+                nanos(log.start)?,
+                nanos(log.end)?,
+                log.done,
+                log.paused,
+                log.notes,
+                serde_json::to_string(&log.segments)?,
+            ],
+            |row| row.get(0),
+        )?;
+
+    tx.execute("DELETE FROM log_tags WHERE log_id = ?1", [id])?;
+    let mut stmt =
+        tx.prepare_cached("INSERT INTO log_tags (log_id, tag) VALUES (?1, ?2)")?;
+    for tag in &log.tags {
+        stmt.execute(params![id, tag])?;
+    }
+    Ok(())
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self) -> Result<Option<SaveData>> {
+        let meta = |key: &str| -> Result<Option<i64>> {
+            let value = self
+                .conn
+                .query_row("SELECT value FROM meta WHERE key = ?1", [key], |r| r.get(0))
+                .optional()?;
+            Ok(value)
+        };
+        let Some(version) = meta("version")? else {
+            return Ok(None);
+        };
+        if version as u64 > CURRENT_VERSION {
+            anyhow::bail!(
+                "database version {version} is newer than supported ({CURRENT_VERSION})"
+            );
+        }
+
+        let mut log_tags: HashMap<i64, HashSet<String>> = HashMap::new();
+        let mut stmt = self.conn.prepare("SELECT log_id, tag FROM log_tags")?;
+        let rows = stmt.query_map([], |r| Ok((r.get(0)?, r.get(1)?)))?;
+        for row in rows {
+            let (id, tag) = row?;
+            log_tags.entry(id).or_default().insert(tag);
+        }
+
+        let mut data = SaveData::new();
+        data.opened_once = meta("opened_once")?.unwrap_or(0) != 0;
+        data.journal_seq = meta("journal_seq")?.unwrap_or(0) as u64;
+        let mut written = Written {
+            meta: Some((data.version, data.opened_once, data.journal_seq)),
+            ..Default::default()
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT id, uid, past, rank, name, start_time, end_time, done, paused, notes,
+                    segments
+             FROM logs ORDER BY past, rank",
+        )?;
+        let mut rows = stmt.query([])?;
+        let mut by_id = HashMap::new();
+        while let Some(r) = rows.next()? {
+            let past: bool = r.get(2)?;
+            let segments: String = r.get(10)?;
+            let log = Log {
+                id: r.get(1)?,
+                name: r.get(4)?,
+                start: from_nanos(r.get(5)?),
+                end: from_nanos(r.get(6)?),
+                done: r.get(7)?,
+                paused: r.get(8)?,
+                notes: r.get(9)?,
+                segments: serde_json::from_str(&segments)
+                    .context("invalid segments in database")
+                    .map_err(corrupt)?,
+                tags: log_tags.remove(&r.get(0)?).unwrap_or_default(),
+            };
+            by_id.insert(log.id.clone(), (past, r.get(3)?, log.clone()));
+            if past {
+                data.past_logs.push(log);
+            } else {
+                data.logs.push(log);
+            }
+        }
+        written.logs = Some(by_id);
+
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, color, refs FROM tags ORDER BY position")?;
+        let tags = stmt
+            .query_map([], |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)))?
+            .collect::<rusqlite::Result<Vec<(String, u32, i32)>>>()?;
+        data.tags = tags
+            .iter()
+            .map(|(name, color, refs)| Tag::new(name, *color, *refs))
+            .collect();
+        written.tags = Some(tags);
+
+        *self.written.borrow_mut() = written;
+        Ok(Some(data))
+    }
+
+    fn save(&self, data: &SaveData) -> Result<()> {
+        let mut written = self.written.borrow_mut();
+        let tx = self.conn.unchecked_transaction()?;
+        let res = self
+            .write(&tx, data, &mut written)
+            .and_then(|_| Ok(tx.commit()?));
+        if res.is_err() {
+            // the cache may no longer match the database
+            *written = Written::default();
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> SaveData {
+        let mut data = SaveData::new();
+        for name in ["a", "b", "c"] {
+            let tags = HashSet::from([format!("tag-{name}")]);
+            for tag in &tags {
+                data.tags.add(tag).refs += 1;
+            }
+            data.logs.push(Log::new(name.to_string(), tags));
+        }
+        let mut done = Log::new("done".to_string(), HashSet::new());
+        done.notes = "notes".to_string();
+        done.done = true;
+        data.past_logs.push(done);
+        data
+    }
+
+    fn open(dir: &tempfile::TempDir) -> SqliteStorage {
+        SqliteStorage::open(&dir.path().join("save.db")).unwrap()
+    }
+
+    #[test]
+    fn sqlite_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let data = sample();
+        open(&dir).save(&data).unwrap();
+
+        let loaded = open(&dir).load().unwrap().unwrap();
+        assert_eq!(loaded.logs, data.logs);
+        assert_eq!(loaded.past_logs, data.past_logs);
+        assert_eq!(loaded.tags, data.tags);
+        assert_eq!(loaded.version, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn sqlite_writes_only_changed_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(&dir);
+        let mut data = sample();
+        storage.save(&data).unwrap();
+
+        let before = storage.conn.total_changes();
+        data.logs[1].name = "renamed".to_string();
+        storage.save(&data).unwrap();
+        // the log row, plus deleting and inserting its tag
+        assert_eq!(storage.conn.total_changes() - before, 3);
+
+        let before = storage.conn.total_changes();
+        let last = data.logs.pop().unwrap();
+        data.logs.insert(0, last);
+        storage.save(&data).unwrap();
+        assert_eq!(storage.conn.total_changes() - before, 3);
+
+        let loaded = open(&dir).load().unwrap().unwrap();
+        assert_eq!(loaded.logs, data.logs);
+    }
+
+    #[test]
+    fn sqlite_removes_deleted_logs() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = open(&dir);
+        let mut data = sample();
+        storage.save(&data).unwrap();
+
+        let log = data.logs.remove(0);
+        data.past_logs.push(log);
+        data.logs.remove(0);
+        storage.save(&data).unwrap();
+
+        let loaded = open(&dir).load().unwrap().unwrap();
+        assert_eq!(loaded.logs, data.logs);
+        assert_eq!(loaded.past_logs, data.past_logs);
+    }
+
+    #[test]
+    fn ranks_keep_ordered_keys() {
+        assert_eq!(ranks(&[None, None]), [1.0, 2.0]);
+        assert_eq!(ranks(&[Some(1.0), None, Some(2.0)]), [1.0, 1.5, 2.0]);
+        assert_eq!(ranks(&[Some(3.0), Some(1.0), Some(2.0)]), [0.0, 1.0, 2.0]);
+        assert_eq!(ranks(&[Some(1.0), Some(2.0), None]), [1.0, 2.0, 3.0]);
+        let tight = [Some(1.0), None, Some(1.0 + f64::EPSILON)];
+        assert_eq!(ranks(&tight), [1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn upgrades_positional_databases() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("save.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE meta (key TEXT PRIMARY KEY, value INTEGER NOT NULL);
+             INSERT INTO meta VALUES ('version', 4);
+             CREATE TABLE logs (
+                 id INTEGER PRIMARY KEY,
+                 past INTEGER NOT NULL,
+                 position INTEGER NOT NULL,
+                 name TEXT NOT NULL,
+                 start_time INTEGER NOT NULL,
+                 end_time INTEGER NOT NULL,
+                 done INTEGER NOT NULL,
+                 paused INTEGER NOT NULL,
+                 notes TEXT NOT NULL,
+                 segments TEXT NOT NULL,
+                 UNIQUE (past, position)
+             );
+             INSERT INTO logs VALUES (1, 0, 0, 'a', 5, 5, 0, 0, '', '[]');
+             INSERT INTO logs VALUES (2, 1, 0, 'b', 5, 9, 1, 0, '', '[]');
+             CREATE TABLE log_tags (
+                 log_id INTEGER NOT NULL REFERENCES logs (id) ON DELETE CASCADE,
+                 tag TEXT NOT NULL,
+                 PRIMARY KEY (log_id, tag)
+             );
+             INSERT INTO log_tags VALUES (2, 'work');",
+        )
+        .unwrap();
+        drop(conn);
+
+        let data = SqliteStorage::open(&path).unwrap().load().unwrap().unwrap();
+        assert_eq!(data.logs[0].id, migrate::legacy_id(5));
+        assert_eq!(data.past_logs[0].id, format!("{}-2", migrate::legacy_id(5)));
+        assert!(data.past_logs[0].tags.contains("work"));
+    }
+}
@@ -1,19 +1,13 @@
-use std::{cell::RefCell, rc::Rc};
-
+use anyhow::Result;
 use ratatui::{
     Frame,
-    crossterm::event::KeyCode,
     layout::Rect,
     style::{Style, Stylize},
-    text::{Line, Span, ToSpan},
+    text::{Line, ToSpan},
     widgets::{Block, BorderType, List, ListState},
 };
 
-use crate::{
-    State,
-    data::{self, SaveData},
-    theme,
-};
+use crate::{State, data::SaveData, keymap::Action, theme};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum ListType {
@@ -21,66 +15,73 @@ pub enum ListType {
     Log,
     Tag,
     PastLog,
+    Report,
 }
 
 pub trait Tab {
     fn render(
-        self: &mut Self,
+        &mut self,
         block: &Block,
         area: &Rect,
         frame: &mut Frame,
         data: &mut SaveData,
     );
-    fn get_title(self: &Self) -> &str;
-    fn get_line(self: &Self) -> Line<'static>;
-    fn handle_keys(self: &mut Self, key: KeyCode, data: &mut SaveData);
-}
-
-impl<'a> ListType {
-    pub const TYPES: [ListType; 3] = [ListType::Log, ListType::Tag, ListType::PastLog];
-
-    fn to_span(self) -> Line<'a> {
-        match self {
-            ListType::Log => {
-                let icon = theme::unicode_icon(0xf02c, theme::BLUE);
-                let name = Span::raw("Logs");
-                Line::from(vec![icon, name])
-            }
-            ListType::PastLog => {
-                let icon = theme::unicode_icon(0xf02c, theme::AQUA);
-                let name = Span::raw("Past Logs");
-                Line::from(vec![icon, name])
-            }
-            ListType::Tag => {
-                let icon = theme::unicode_icon(0xf02c, theme::RED);
-                let name = Span::raw("Tags");
-                Line::from(vec![icon, name])
-            }
-        }
+    fn get_title(&self) -> &str;
+    fn get_line(&self) -> Line<'static>;
+    /// Actions available while this tab is focused, with what they do here.
+    /// Only these are dispatched to the tab and they make up its help.
+    fn actions(&self) -> &'static [(Action, &'static str)];
+    /// Question asked before running `action` when the config wants it
+    /// confirmed.
+    fn confirm_msg(&self, action: Action, _data: &SaveData) -> String {
+        let desc = self
+            .actions()
+            .iter()
+            .find(|(a, _)| *a == action)
+            .map_or(action.name(), |(_, d)| d);
+        format!("Really {desc}?")
     }
-    pub fn to_str(self) -> &'a str {
-        match self {
-            ListType::Log => "| Logs |",
-            ListType::Tag => "| Tags |",
-            ListType::PastLog => "| Past Logs |",
-        }
+    /// Returns a description of the change if `data` was modified.
+    fn handle_action(&mut self, action: Action, data: &mut SaveData) -> Option<String>;
+    fn selected(&self) -> Option<usize>;
+    /// Identifies the selected item so it can be found again after the
+    /// data is reloaded.
+    fn selection_id(&self, _data: &SaveData) -> Option<String> {
+        None
     }
+    /// Selects the item `id` names and returns true, or keeps the selection
+    /// in bounds and returns false if it is gone.
+    fn reselect(&mut self, _id: &str, _data: &SaveData) -> bool {
+        false
+    }
+    fn handle_input(&mut self, _input: String, _data: &mut SaveData) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl ListType {
+    pub const TYPES: [ListType; 4] = [
+        ListType::Log,
+        ListType::Tag,
+        ListType::PastLog,
+        ListType::Report,
+    ];
 }
 
 pub fn render_tab_list(area: &Rect, state: &State, frame: &mut Frame) {
     let tab_block = Block::bordered()
         .border_type(BorderType::Rounded)
-        .fg(theme::ORANGE)
-        .bg(theme::BG0)
+        .fg(theme::orange())
+        .bg(theme::bg0())
         .title("| Tabs |".to_span().into_centered_line());
 
-    let tab_lines = ListType::TYPES.iter().map(|t| t.to_span());
+    let tab_lines = state.rendered_lists.iter().map(|t| t.get_line());
 
     let tab_list = List::new(tab_lines)
         .block(tab_block)
-        .fg(theme::TEXT)
-        .bg(theme::BG0)
-        .highlight_style(Style::default().bg(theme::BG1));
+        .fg(theme::text())
+        .bg(theme::bg0())
+        .highlight_style(Style::default().bg(theme::bg1()));
 
     let mut st = ListState::default().with_selected(Some(state.focused_list_idx));
 
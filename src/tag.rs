@@ -1,19 +1,29 @@
-use crate::{State, theme};
+use crate::{
+    State,
+    data::SaveData,
+    keymap::Action,
+    log::{Log, duration_as_hhmmss},
+    tab::Tab,
+    theme,
+};
+use anyhow::{Context, Result};
+use chrono::TimeDelta;
 use ratatui::{
     Frame,
     layout::Rect,
     style::{Color, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, List, ListItem},
+    widgets::{Block, List, ListItem, ListState},
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use tachyonfx::ToRgbComponents;
 #[allow(unused_imports)]
 use tracing::{info, warn};
 
-#[derive(Default, Serialize, Deserialize, Debug, Clone)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Tag {
     name: String,
     color: u32,
@@ -21,19 +31,38 @@ pub struct Tag {
 }
 
 impl Tag {
+    pub fn new(name: &str, color: u32, refs: i32) -> Tag {
+        Tag {
+            name: name.to_string(),
+            color,
+            refs,
+        }
+    }
+
     pub fn color(&self) -> &u32 {
         &self.color
     }
     pub fn name(&self) -> &str {
         &self.name
     }
+    pub fn set_color(&mut self, color: u32) {
+        self.color = color;
+    }
 }
 
-#[derive(Default, Serialize, Deserialize, Debug)]
+#[derive(Default, Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TagSys {
     tags: Vec<Tag>,
 }
 
+impl FromIterator<Tag> for TagSys {
+    fn from_iter<I: IntoIterator<Item = Tag>>(iter: I) -> Self {
+        TagSys {
+            tags: iter.into_iter().collect(),
+        }
+    }
+}
+
 impl TagSys {
     pub fn add(&mut self, name: &str) -> &mut Tag {
         if let Some(i) = self.tags.iter().position(|t| t.name() == name) {
@@ -42,7 +71,7 @@ impl TagSys {
 
         let t = Tag {
             name: name.to_string(),
-            color: 0x00ff0000,
+            color: theme::next_tag_color(self.tags.iter().map(|t| t.color)),
             refs: 0,
         };
         self.tags.push(t);
@@ -68,6 +97,139 @@ impl TagSys {
     pub fn tags(&self) -> &Vec<Tag> {
         &self.tags
     }
+
+    /// Recomputes `refs` from the tags `logs` actually use, registering
+    /// missing tags and dropping unused ones. Returns whether anything was
+    /// out of sync.
+    pub fn rebuild_refs<'a>(&mut self, logs: impl Iterator<Item = &'a Log>) -> bool {
+        let mut counts: HashMap<&str, i32> = HashMap::new();
+        for log in logs {
+            for t in &log.tags {
+                *counts.entry(t.as_str()).or_default() += 1;
+            }
+        }
+
+        let mut changed = false;
+        for (name, n) in &counts {
+            let tag = self.add(name);
+            changed |= tag.refs != *n;
+            tag.refs = *n;
+        }
+        let before = self.tags.len();
+        self.tags.retain(|t| counts.contains_key(t.name()));
+        changed || self.tags.len() != before
+    }
+}
+
+/// Moves every tag at or under `from` to the same place under `to` in all
+/// logs, e.g. `a:b` becomes `c:b` when moving `a` to `c`.
+fn retag(data: &mut SaveData, from: &str, to: &str) {
+    let moved = |t: &str| match t.strip_prefix(from) {
+        Some(rest) if is_under(t, from) => format!("{to}{rest}"),
+        _ => t.to_string(),
+    };
+    for log in data.logs.iter_mut().chain(data.past_logs.iter_mut()) {
+        log.tags = log.tags.iter().map(|t| moved(t)).collect();
+    }
+    for tag in data.tags.tags.iter_mut() {
+        tag.name = moved(&tag.name);
+    }
+}
+
+fn exists_under(data: &SaveData, path: &str) -> bool {
+    data.tags.tags().iter().any(|t| is_under(t.name(), path))
+}
+
+/// Renames `old` and its descendants everywhere, including past logs.
+pub fn rename(data: &mut SaveData, old: &str, new: &str) -> Result<()> {
+    if old == new {
+        return Ok(());
+    }
+    if exists_under(data, new) {
+        anyhow::bail!("tag '{new}' already exists, merge instead");
+    }
+    retag(data, old, new);
+    Ok(())
+}
+
+/// Merges `from` and its descendants into `into`; tags that end up on a log
+/// twice collapse into one and `into` keeps its own color.
+pub fn merge(data: &mut SaveData, from: &str, into: &str) -> Result<()> {
+    if is_under(into, from) {
+        anyhow::bail!("can't merge '{from}' into itself");
+    }
+    let colors: HashMap<String, u32> = data
+        .tags
+        .tags()
+        .iter()
+        .filter(|t| is_under(t.name(), into))
+        .map(|t| (t.name.clone(), t.color))
+        .collect();
+    retag(data, from, into);
+
+    // keep the first entry of each name, preferring the target's color
+    let mut seen = HashSet::new();
+    data.tags.tags.retain(|t| seen.insert(t.name.clone()));
+    for tag in data.tags.tags.iter_mut() {
+        if let Some(color) = colors.get(&tag.name) {
+            tag.color = *color;
+        }
+    }
+    let SaveData {
+        logs,
+        past_logs,
+        tags,
+        ..
+    } = data;
+    tags.rebuild_refs(logs.iter().chain(past_logs.iter()));
+    Ok(())
+}
+
+/// Deletes `name` and its descendants. With `strip` the tags are removed
+/// from their logs, otherwise the logs are deleted along with them. Returns
+/// the number of logs affected.
+pub fn delete(data: &mut SaveData, name: &str, strip: bool) -> usize {
+    let tagged = |l: &Log| l.tags.iter().any(|t| is_under(t, name));
+    let mut n = 0;
+    for logs in [&mut data.logs, &mut data.past_logs] {
+        if strip {
+            for log in logs.iter_mut().filter(|l| tagged(l)) {
+                log.tags.retain(|t| !is_under(t, name));
+                n += 1;
+            }
+        } else {
+            let before = logs.len();
+            logs.retain(|l| !tagged(l));
+            n += before - logs.len();
+        }
+    }
+    let SaveData {
+        logs,
+        past_logs,
+        tags,
+        ..
+    } = data;
+    tags.tags.retain(|t| !is_under(t.name(), name));
+    tags.rebuild_refs(logs.iter().chain(past_logs.iter()));
+    n
+}
+
+/// A tag name: words joined by `:` or `/` into a path such as
+/// `client:project:task`. Parents along the path are implicit.
+pub const NAME_PATTERN: &str = r"\w+(?:[:/]\w+)*";
+
+/// Stores paths with `:` only, so `a/b` and `a:b` are the same tag.
+pub fn normalize(name: &str) -> String {
+    name.split([':', '/'])
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Whether `tag` is `parent` itself or one of its descendants.
+pub fn is_under(tag: &str, parent: &str) -> bool {
+    tag.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(':'))
 }
 
 fn rgb_to_hex(color: (u8, u8, u8)) -> u32 {
@@ -75,74 +237,281 @@ fn rgb_to_hex(color: (u8, u8, u8)) -> u32 {
     ((r as u32) << 16) | ((g as u32) << 8) | (b as u32)
 }
 
-pub fn handle_edit(state: &mut State, input: String) {
+/// Renames and/or recolors the selected tag from `<name>(: <color>)?`.
+/// Returns a description of the edit if the tag was changed.
+pub fn handle_edit(
+    state: &mut State,
+    data: &mut SaveData,
+    input: String,
+) -> Option<String> {
     let color_regex: &str = &theme::TERM_COLORS_REGEX;
-    let full = format!(r"^\w+:\s((#\w{{6}})|{color_regex})");
+    let full =
+        format!(r"^({NAME_PATTERN})(:\s((#[[:xdigit:]]{{6}})|{color_regex}))?\s*$");
     let check = Regex::new(&full).unwrap();
-    if !check.is_match(&input) {
+    let Some(caps) = check.captures(&input) else {
         warn!("Wrong format for tag edit");
         info!("regex: {}", full);
-        state.popup_msg = Span::styled("Bad Input", theme::RED);
-        state.popup_active = true;
-        return;
-    }
-
-    let tag = state
-        .data
-        .tags
-        .tags
-        .get_mut(state.list_state.selected().unwrap())
-        .unwrap();
-
-    let (new_name, color_str_org) = input.split_once(":").unwrap();
-    let color_str = color_str_org.replace(" #", "");
-    let color = if color_str_org.contains("#") {
-        u32::from_str_radix(&color_str, 16).unwrap()
-    } else {
-        info!("color str: {}", color_str);
-        rgb_to_hex(theme::TERM_COLORS.get(color_str.trim()).unwrap().to_rgb())
+        state.show_popup(Span::styled("Bad Input", theme::red()));
+        return None;
     };
 
-    let iter = state
-        .data
-        .logs
-        .iter_mut()
-        .filter(|l| l.tags.contains(tag.name()));
-    for log in iter {
-        log.tags.remove(tag.name());
-        log.tags.insert(new_name.to_string());
+    let i = state
+        .rendered_lists
+        .get(state.focused_list_idx)
+        .and_then(|t| t.selected())?;
+    let old = data.tags.tags.get(i)?.name.clone();
+    let new_name = normalize(&caps[1]);
+
+    if let Err(e) = rename(data, &old, &new_name) {
+        state.show_popup(Span::styled(e.to_string(), theme::red()));
+        return None;
     }
 
-    tag.name = new_name.to_string();
-    tag.color = color;
+    if let Some(color_str) = caps.get(3).map(|m| m.as_str()) {
+        let color = match color_str.strip_prefix('#') {
+            Some(hex) => u32::from_str_radix(hex, 16).unwrap(),
+            None => {
+                info!("color str: {}", color_str);
+                rgb_to_hex(theme::TERM_COLORS.get(color_str).unwrap()().to_rgb())
+            }
+        };
+        data.tags.add(&new_name).color = color;
+    }
+    Some(format!("Edited tag '{old}'"))
 }
 
-pub fn render_tag_list(
+/// Merges the selected tag into the tag named by `input`.
+pub fn handle_merge(
     state: &mut State,
-    outer_block: &Block,
-    area: &Rect,
-    frame: &mut Frame,
-) {
-    let list = List::new(state.data.tags.tags().iter().map(|l| {
-        let name = l.name();
-        let icon = theme::unicode_icon(0xf1224, Color::from_u32(*l.color()));
-        let ln = Line::from(vec![
-            icon,
-            Span::raw(name),
-            Span::styled(
-                format!("{}{}", " ".repeat(20 - name.len()), l.refs),
-                theme::BLUE,
-            ),
-        ]);
-
-        let color = theme::BG0;
-        ListItem::from(ln).bg(color)
-    }))
-    .block(outer_block.clone())
-    .fg(theme::TEXT)
-    .bg(theme::BG0)
-    .highlight_style(Style::default().bg(theme::BG1))
-    .highlight_symbol("> ");
-
-    frame.render_stateful_widget(list, *area, &mut state.list_state);
+    data: &mut SaveData,
+    input: &str,
+) -> Option<String> {
+    let i = state.rendered_lists[state.focused_list_idx].selected()?;
+    let from = data.tags.tags.get(i)?.name.clone();
+    let into = normalize(input.trim());
+    let res = Regex::new(&format!("^{NAME_PATTERN}$"))
+        .unwrap()
+        .is_match(&into)
+        .then_some(())
+        .context("invalid tag name")
+        .and_then(|_| merge(data, &from, &into));
+    match res {
+        Err(e) => {
+            state.show_popup(Span::styled(e.to_string(), theme::red()));
+            None
+        }
+        _ => Some(format!("Merged '{from}' into '{into}'")),
+    }
+}
+
+/// A row of the tag tree; parents without a tag of their own are implicit.
+#[derive(Debug, Clone)]
+struct Node {
+    path: String,
+    depth: usize,
+    /// Index into `TagSys::tags` if the path is a tag itself.
+    tag: Option<usize>,
+    has_children: bool,
+    /// Refs and duration of the whole subtree.
+    refs: i32,
+    duration: TimeDelta,
+}
+
+/// Flattens all tags and their implicit parents into a depth-first tree.
+fn build_tree(data: &SaveData) -> Vec<Node> {
+    let mut nodes: BTreeMap<Vec<&str>, Node> = BTreeMap::new();
+    for (i, tag) in data.tags.tags().iter().enumerate() {
+        let parts: Vec<&str> = tag.name().split(':').collect();
+        for depth in 0..parts.len() {
+            let node = nodes.entry(parts[..=depth].to_vec()).or_insert(Node {
+                path: parts[..=depth].join(":"),
+                depth,
+                tag: None,
+                has_children: false,
+                refs: 0,
+                duration: TimeDelta::zero(),
+            });
+            node.has_children |= depth + 1 < parts.len();
+            if depth + 1 == parts.len() {
+                node.tag = Some(i);
+            }
+        }
+    }
+
+    // every log counts once towards each node its tags fall under
+    for log in data.logs.iter().chain(data.past_logs.iter()) {
+        let mut under: HashSet<Vec<&str>> = HashSet::new();
+        for tag in &log.tags {
+            let parts: Vec<&str> = tag.split(':').collect();
+            under.extend((1..=parts.len()).map(|n| parts[..n].to_vec()));
+        }
+        let duration = log.duration();
+        for path in under {
+            if let Some(node) = nodes.get_mut(&path) {
+                node.refs += 1;
+                node.duration += duration;
+            }
+        }
+    }
+    nodes.into_values().collect()
+}
+
+pub struct TagList {
+    title: String,
+    pub state: ListState,
+    /// Paths of collapsed nodes.
+    collapsed: HashSet<String>,
+    /// Visible rows as of the last render.
+    rows: Vec<Node>,
+}
+
+impl TagList {
+    pub fn new(title: &str) -> Self {
+        Self {
+            title: format!("| {} |", title),
+            state: ListState::default(),
+            collapsed: HashSet::new(),
+            rows: vec![],
+        }
+    }
+
+    fn toggle_selected(&mut self) {
+        let Some(node) = self.state.selected().and_then(|i| self.rows.get(i)) else {
+            return;
+        };
+        if node.has_children && !self.collapsed.remove(&node.path) {
+            self.collapsed.insert(node.path.clone());
+        }
+    }
+
+    /// Rows of the tag tree, without the children of collapsed nodes.
+    fn visible_rows(&self, data: &SaveData) -> Vec<Node> {
+        build_tree(data)
+            .into_iter()
+            .filter(|n| {
+                !self
+                    .collapsed
+                    .iter()
+                    .any(|c| n.path != *c && is_under(&n.path, c))
+            })
+            .collect()
+    }
+}
+
+impl Tab for TagList {
+    fn render(
+        &mut self,
+        blk: &Block,
+        area: &Rect,
+        frame: &mut Frame,
+        data: &mut SaveData,
+    ) {
+        self.rows = self.visible_rows(data);
+
+        let tags = data.tags.tags();
+        let list = List::new(self.rows.iter().map(|n| {
+            let fold = match (n.has_children, self.collapsed.contains(&n.path)) {
+                (false, _) => "  ",
+                (true, false) => "▾ ",
+                (true, true) => "▸ ",
+            };
+            let color = n
+                .tag
+                .map_or(theme::text_alt(), |i| Color::from_u32(*tags[i].color()));
+            let name = n.path.rsplit(':').next().unwrap_or_default().to_string();
+            let width = 2 * n.depth + name.chars().count();
+            let ln = Line::from(vec![
+                Span::raw(format!("{}{fold}", "  ".repeat(n.depth))),
+                theme::unicode_icon(0xf1224, color),
+                Span::raw(name),
+                Span::styled(
+                    format!(
+                        "{}{:>4}",
+                        " ".repeat(24usize.saturating_sub(width)),
+                        n.refs
+                    ),
+                    theme::blue(),
+                ),
+                Span::styled(
+                    format!("  {}", duration_as_hhmmss(n.duration)),
+                    theme::text_alt(),
+                ),
+            ]);
+
+            ListItem::from(ln).bg(theme::bg0())
+        }))
+        .block(blk.clone())
+        .fg(theme::text())
+        .bg(theme::bg0())
+        .highlight_style(Style::default().bg(theme::bg1()))
+        .highlight_symbol("> ");
+
+        frame.render_stateful_widget(list, *area, &mut self.state);
+    }
+
+    fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    fn get_line(&self) -> Line<'static> {
+        let icon = theme::unicode_icon(0xf02c, theme::red());
+        let name = Span::raw("Tags");
+        Line::from(vec![icon, name])
+    }
+
+    fn actions(&self) -> &'static [(Action, &'static str)] {
+        &[
+            (Action::Complete, "rename / recolor"),
+            (Action::MergeTag, "merge into"),
+            (Action::Delete, "delete"),
+            (Action::StripTag, "delete, strip from logs"),
+            (Action::DeleteTagged, "delete with its logs"),
+            (Action::ToggleFold, "fold / unfold"),
+            (Action::NextItem, "next"),
+            (Action::PrevItem, "previous"),
+            (Action::FirstItem, "first"),
+            (Action::LastItem, "last"),
+        ]
+    }
+
+    fn handle_action(&mut self, action: Action, data: &mut SaveData) -> Option<String> {
+        match action {
+            Action::StripTag | Action::DeleteTagged => {
+                let name = data.tags.tags().get(self.selected()?)?.name.clone();
+                let n = delete(data, &name, action == Action::StripTag);
+                return Some(format!("Deleted tag '{name}' ({n} logs)"));
+            }
+            Action::NextItem => self.state.select_next(),
+            Action::PrevItem => self.state.select_previous(),
+            Action::FirstItem => self.state.select_first(),
+            Action::LastItem => self.state.select_last(),
+            Action::ToggleFold => self.toggle_selected(),
+            _ => {}
+        }
+        None
+    }
+
+    /// Index into `TagSys::tags` of the selected row, `None` for implicit
+    /// parents.
+    fn selected(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.rows.get(i))
+            .and_then(|n| n.tag)
+    }
+
+    fn selection_id(&self, _data: &SaveData) -> Option<String> {
+        let node = self.rows.get(self.state.selected()?)?;
+        Some(node.path.clone())
+    }
+
+    fn reselect(&mut self, id: &str, data: &SaveData) -> bool {
+        self.rows = self.visible_rows(data);
+        let rows = &self.rows;
+        let found = rows.iter().position(|n| n.path == id);
+        let i = found
+            .or_else(|| Some(self.state.selected()?.min(rows.len().checked_sub(1)?)));
+        self.state.select(i);
+        found.is_some()
+    }
 }
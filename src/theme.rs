@@ -1,30 +1,139 @@
 use ratatui::{style::Color, text::Span};
 
 use phf_macros::phf_map;
+use std::sync::OnceLock;
 
-pub const TEXT: Color = Color::from_u32(0x00ebdbb2);
-pub const TEXT_ALT: Color = Color::from_u32(0x00a89984);
-pub const BG0: Color = Color::from_u32(0x00282828);
-pub const BG1: Color = Color::from_u32(0x003c3836);
-
-pub const GRAY: Color = Color::from_u32(0x928374);
-pub const RED: Color = Color::from_u32(0xfb4934);
-pub const GREEN: Color = Color::from_u32(0xb8bb26);
-pub const YELLOW: Color = Color::from_u32(0xfabd2f);
-pub const BLUE: Color = Color::from_u32(0x83a598);
-pub const PURPLE: Color = Color::from_u32(0xd3869b);
-pub const AQUA: Color = Color::from_u32(0x8ec07c);
-pub const ORANGE: Color = Color::from_u32(0x00fe8019);
-
-pub const TERM_COLORS: phf::Map<&'static str, Color> = phf_map! {
-    "Red" => RED,
-    "Green" => GREEN,
-    "Yellow" => YELLOW,
-    "Blue" => BLUE,
-    "Purple" => PURPLE,
-    "Aqua" => AQUA,
-    "Gray" => GRAY,
-    "Orange" => ORANGE,
+#[derive(Debug, Clone, Copy)]
+pub struct Palette {
+    pub text: Color,
+    pub text_alt: Color,
+    pub bg0: Color,
+    pub bg1: Color,
+    pub gray: Color,
+    pub red: Color,
+    pub green: Color,
+    pub yellow: Color,
+    pub blue: Color,
+    pub purple: Color,
+    pub aqua: Color,
+    pub orange: Color,
+}
+
+pub const GRUVBOX: Palette = Palette {
+    text: Color::from_u32(0x00ebdbb2),
+    text_alt: Color::from_u32(0x00a89984),
+    bg0: Color::from_u32(0x00282828),
+    bg1: Color::from_u32(0x003c3836),
+    gray: Color::from_u32(0x928374),
+    red: Color::from_u32(0xfb4934),
+    green: Color::from_u32(0xb8bb26),
+    yellow: Color::from_u32(0xfabd2f),
+    blue: Color::from_u32(0x83a598),
+    purple: Color::from_u32(0xd3869b),
+    aqua: Color::from_u32(0x8ec07c),
+    orange: Color::from_u32(0x00fe8019),
+};
+
+pub const GRUVBOX_LIGHT: Palette = Palette {
+    text: Color::from_u32(0x003c3836),
+    text_alt: Color::from_u32(0x007c6f64),
+    bg0: Color::from_u32(0x00fbf1c7),
+    bg1: Color::from_u32(0x00ebdbb2),
+    gray: Color::from_u32(0x928374),
+    red: Color::from_u32(0x9d0006),
+    green: Color::from_u32(0x79740e),
+    yellow: Color::from_u32(0xb57614),
+    blue: Color::from_u32(0x076678),
+    purple: Color::from_u32(0x8f3f71),
+    aqua: Color::from_u32(0x427b58),
+    orange: Color::from_u32(0x00af3a03),
+};
+
+pub const CATPPUCCIN: Palette = Palette {
+    text: Color::from_u32(0x00cdd6f4),
+    text_alt: Color::from_u32(0x00a6adc8),
+    bg0: Color::from_u32(0x001e1e2e),
+    bg1: Color::from_u32(0x00313244),
+    gray: Color::from_u32(0x6c7086),
+    red: Color::from_u32(0xf38ba8),
+    green: Color::from_u32(0xa6e3a1),
+    yellow: Color::from_u32(0xf9e2af),
+    blue: Color::from_u32(0x89b4fa),
+    purple: Color::from_u32(0xcba6f7),
+    aqua: Color::from_u32(0x94e2d5),
+    orange: Color::from_u32(0x00fab387),
+};
+
+pub const THEMES: phf::Map<&'static str, Palette> = phf_map! {
+    "gruvbox" => GRUVBOX,
+    "gruvbox-light" => GRUVBOX_LIGHT,
+    "catppuccin" => CATPPUCCIN,
+};
+
+static ACTIVE: OnceLock<Palette> = OnceLock::new();
+
+/// Selects the palette used for the rest of the run. Only the first call has
+/// an effect; without one the gruvbox palette is used.
+pub fn set(name: &str) -> anyhow::Result<()> {
+    let Some(palette) = THEMES.get(name) else {
+        let mut names: Vec<&str> = THEMES.keys().copied().collect();
+        names.sort();
+        anyhow::bail!("unknown theme '{name}' (available: {})", names.join(", "));
+    };
+    let _ = ACTIVE.set(*palette);
+    Ok(())
+}
+
+pub fn palette() -> &'static Palette {
+    ACTIVE.get_or_init(|| GRUVBOX)
+}
+
+pub fn text() -> Color {
+    palette().text
+}
+pub fn text_alt() -> Color {
+    palette().text_alt
+}
+pub fn bg0() -> Color {
+    palette().bg0
+}
+pub fn bg1() -> Color {
+    palette().bg1
+}
+pub fn gray() -> Color {
+    palette().gray
+}
+pub fn red() -> Color {
+    palette().red
+}
+pub fn green() -> Color {
+    palette().green
+}
+pub fn yellow() -> Color {
+    palette().yellow
+}
+pub fn blue() -> Color {
+    palette().blue
+}
+pub fn purple() -> Color {
+    palette().purple
+}
+pub fn aqua() -> Color {
+    palette().aqua
+}
+pub fn orange() -> Color {
+    palette().orange
+}
+
+pub const TERM_COLORS: phf::Map<&'static str, fn() -> Color> = phf_map! {
+    "Red" => red,
+    "Green" => green,
+    "Yellow" => yellow,
+    "Blue" => blue,
+    "Purple" => purple,
+    "Aqua" => aqua,
+    "Gray" => gray,
+    "Orange" => orange,
 };
 
 lazy_static::lazy_static! {
@@ -34,6 +143,108 @@ lazy_static::lazy_static! {
     );
 }
 
+/// Colors closer than this (CIE76 ΔE) are hard to tell apart.
+const MIN_TAG_DISTANCE: f64 = 25.0;
+
+fn rgb(color: Color) -> (u8, u8, u8) {
+    match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        _ => (0, 0, 0),
+    }
+}
+
+fn from_u32(c: u32) -> (u8, u8, u8) {
+    ((c >> 16) as u8, (c >> 8) as u8, c as u8)
+}
+
+fn to_u32((r, g, b): (u8, u8, u8)) -> u32 {
+    ((r as u32) << 16) | ((g as u32) << 8) | b as u32
+}
+
+/// sRGB to CIE L*a*b* (D65).
+fn lab((r, g, b): (u8, u8, u8)) -> (f64, f64, f64) {
+    let lin = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (lin(r), lin(g), lin(b));
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| {
+        if t > 0.008856 {
+            t.cbrt()
+        } else {
+            7.787 * t + 16.0 / 116.0
+        }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> f64 {
+    let (a, b) = (lab(a), lab(b));
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+fn hsv((h, s, v): (f64, f64, f64)) -> (u8, u8, u8) {
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 / 60 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    let to = |c: f64| ((c + m) * 255.0).round() as u8;
+    (to(r), to(g), to(b))
+}
+
+/// Picks a color for a new tag: the first accent of the active palette that
+/// is far enough from every color in `used`, or else the generated hue that
+/// is furthest from all of them.
+pub fn next_tag_color(used: impl IntoIterator<Item = u32>) -> u32 {
+    let p = palette();
+    let used: Vec<(u8, u8, u8)> = used.into_iter().map(from_u32).collect();
+    let bg = rgb(p.bg0);
+    let nearest = |c: (u8, u8, u8)| {
+        used.iter()
+            .map(|u| distance(c, *u))
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    let accents = [p.red, p.green, p.yellow, p.blue, p.purple, p.aqua, p.orange];
+    if let Some(c) = accents
+        .into_iter()
+        .map(rgb)
+        .find(|c| nearest(*c) >= MIN_TAG_DISTANCE)
+    {
+        return to_u32(c);
+    }
+
+    // generated hues, kept readable against the background
+    let dark = lab(bg).0 < 50.0;
+    let value = if dark { 0.95 } else { 0.6 };
+    (0..72)
+        .flat_map(|i| [0.45, 0.7].map(|s| hsv((i as f64 * 5.0, s, value))))
+        .filter(|c| {
+            if dark {
+                lab(*c).0 >= 60.0
+            } else {
+                lab(*c).0 <= 55.0
+            }
+        })
+        .max_by(|a, b| nearest(*a).total_cmp(&nearest(*b)))
+        .map_or(to_u32(rgb(p.orange)), to_u32)
+}
+
 pub fn unicode_icon<'a>(icon: u32, color: Color) -> Span<'a> {
     let mut c = String::from(char::from_u32(icon).unwrap_or('X'));
     c.push(' ');